@@ -0,0 +1,152 @@
+//! Assembly AST for x86-64, mirroring the subset of instructions we generate.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmProgram {
+    pub functions: Vec<AsmFunction>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmFunction {
+    pub name: String,
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Mov {
+        src: Operand,
+        dst: Operand,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Operand,
+    },
+    /// Computes `dst = dst <op> src`
+    Binary {
+        op: BinaryOp,
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sets the flags from `dst - src`
+    Cmp {
+        src: Operand,
+        dst: Operand,
+    },
+    /// Divides `%edx:%eax` by the operand, leaving the quotient in `%eax` and the
+    /// remainder in `%edx`
+    Idiv(Operand),
+    /// Sign extends `%eax` into `%edx:%eax`
+    Cdq,
+    Jmp(String),
+    /// Jumps to `target` if the flags satisfy `cond`
+    JmpCC {
+        cond: ConditionCode,
+        target: String,
+    },
+    /// Sets the low byte of the operand to 1 if the flags satisfy `cond`, and
+    /// otherwise to 0
+    SetCC {
+        cond: ConditionCode,
+        operand: Operand,
+    },
+    /// Zero extends the low byte of `src` into `dst`
+    MovZeroExtend {
+        src: Operand,
+        dst: Operand,
+    },
+    Label(String),
+    /// Pushes the operand onto the stack as 8 bytes
+    Push(Operand),
+    Call(String),
+    /// Reserves this many bytes of stack, in the function prologue or to align the
+    /// stack for a call
+    AllocateStack(i32),
+    /// Releases this many bytes of stack, after a call
+    DeallocateStack(i32),
+    Ret,
+}
+
+impl Instruction {
+    /// Returns the operands of this instruction, for passes that rewrite them
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Mov { src, dst } => vec![src, dst],
+            Instruction::Unary { operand, .. } => vec![operand],
+            Instruction::Binary { src, dst, .. }
+            | Instruction::Cmp { src, dst }
+            | Instruction::MovZeroExtend { src, dst } => vec![src, dst],
+            Instruction::Idiv(operand)
+            | Instruction::SetCC { operand, .. }
+            | Instruction::Push(operand) => vec![operand],
+            Instruction::Cdq
+            | Instruction::Jmp(_)
+            | Instruction::JmpCC { .. }
+            | Instruction::Label(_)
+            | Instruction::Call(_)
+            | Instruction::AllocateStack(_)
+            | Instruction::DeallocateStack(_)
+            | Instruction::Ret => vec![],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mult,
+    And,
+    Or,
+    Xor,
+    /// Left shift
+    Sal,
+    /// Arithmetic right shift
+    Sar,
+}
+
+/// Conditions on the flags set by [`Instruction::Cmp`], comparing `dst` to `src`
+/// as signed integers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConditionCode {
+    /// Equal
+    E,
+    /// Not equal
+    NE,
+    /// Greater than
+    G,
+    /// Greater than or equal
+    GE,
+    /// Less than
+    L,
+    /// Less than or equal
+    LE,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Imm(i64),
+    Reg(Reg),
+    /// A TACKY variable that has not been assigned a location yet
+    Pseudo(String),
+    /// Offset from `%rbp`
+    Stack(i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reg {
+    AX,
+    CX,
+    DX,
+    DI,
+    SI,
+    R8,
+    R9,
+    R10,
+    R11,
+}
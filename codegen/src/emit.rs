@@ -0,0 +1,653 @@
+//! Emission of the assembly AST as GNU assembler (AT&T syntax) text.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::asm::{
+    AsmFunction, AsmProgram, BinaryOp, ConditionCode, Instruction, Operand, Reg, UnaryOp,
+};
+
+/// Renders `program` as the contents of a `.s` file.
+///
+/// # Panics
+///
+/// Panics if any [`Operand::Pseudo`] is left in `program`.
+pub fn emit(program: &AsmProgram) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        // Writing to a String cannot fail
+        emit_function(&mut out, function, None, false).unwrap();
+    }
+    emit_footer(&mut out);
+    out
+}
+
+/// Like [`emit`], but with CFI directives describing the stack frame of each
+/// function, so debuggers and unwinders can walk the stack through it.
+pub fn emit_with_cfi(program: &AsmProgram) -> String {
+    let mut out = String::new();
+    for function in &program.functions {
+        // Writing to a String cannot fail
+        emit_function(&mut out, function, None, true).unwrap();
+    }
+    emit_footer(&mut out);
+    out
+}
+
+/// Where in the source the functions of a program are defined, for
+/// [`emit_with_lines`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineInfo {
+    /// Paths of the source files, as a debugger should open them. The first is file
+    /// number 1.
+    pub files: Vec<String>,
+    /// 1-based file number and line each function is defined on, by name
+    pub function_lines: HashMap<String, (usize, usize)>,
+}
+
+/// Like [`emit_with_cfi`], but with `.file` and `.loc` directives mapping the start
+/// of each function in `lines` to the line it is defined on, which is enough for a
+/// debugger to show the source and break on a function.
+///
+/// Instructions don't know where in the source they came from, so the rest of
+/// each function is attributed to that line too.
+pub fn emit_with_lines(program: &AsmProgram, lines: &LineInfo) -> String {
+    let mut out = String::new();
+    for (i, file) in lines.files.iter().enumerate() {
+        // Writing to a String cannot fail
+        writeln!(out, "\t.file {} {file:?}", i + 1).unwrap();
+    }
+    for function in &program.functions {
+        let line = lines.function_lines.get(&function.name).copied();
+        emit_function(&mut out, function, line, true).unwrap();
+    }
+    emit_footer(&mut out);
+    out
+}
+
+/// Like [`emit`], but only the function called `name` is emitted. Every other
+/// function is declared `.extern` instead.
+///
+/// Returns `None` if `program` has no function called `name`.
+pub fn emit_only(program: &AsmProgram, name: &str) -> Option<String> {
+    let function = program.functions.iter().find(|f| f.name == name)?;
+
+    let mut out = String::new();
+    for other in program.functions.iter().filter(|f| f.name != name) {
+        out.push_str(&format!("\t.extern {}\n", symbol(&other.name)));
+    }
+    // Writing to a String cannot fail
+    emit_function(&mut out, function, None, false).unwrap();
+    emit_footer(&mut out);
+    Some(out)
+}
+
+fn emit_footer(out: &mut String) {
+    if cfg!(target_os = "linux") {
+        // Tells the linker we don't need an executable stack
+        out.push_str("\t.section .note.GNU-stack,\"\",@progbits\n");
+    }
+}
+
+/// Emits `function`, marking it as starting on the given file number and line, if
+/// any, and describing its stack frame with CFI directives if `cfi` is set.
+fn emit_function(
+    out: &mut String,
+    function: &AsmFunction,
+    line: Option<(usize, usize)>,
+    cfi: bool,
+) -> fmt::Result {
+    let name = symbol(&function.name);
+    writeln!(out, "\t.globl {name}")?;
+    writeln!(out, "{name}:")?;
+    if cfi {
+        out.push_str("\t.cfi_startproc\n");
+    }
+    if let Some((file, line)) = line {
+        writeln!(out, "\t.loc {file} {line}")?;
+    }
+    out.push_str("\tpushq\t%rbp\n");
+    if cfi {
+        // The call pushed the return address, and the prologue the caller's %rbp
+        out.push_str("\t.cfi_def_cfa_offset 16\n");
+        out.push_str("\t.cfi_offset %rbp, -16\n");
+    }
+    out.push_str("\tmovq\t%rsp, %rbp\n");
+    if cfi {
+        // From here on, the frame is found through %rbp, however %rsp moves
+        out.push_str("\t.cfi_def_cfa_register %rbp\n");
+    }
+    for instruction in &function.instructions {
+        emit_instruction(out, instruction, cfi)?;
+    }
+    if cfi {
+        out.push_str("\t.cfi_endproc\n");
+    }
+    out.push('\n');
+    Ok(())
+}
+
+/// Emits `instruction`, updating the CFI directives if `cfi` is set and it changes
+/// the stack frame.
+fn emit_instruction(out: &mut String, instruction: &Instruction, cfi: bool) -> fmt::Result {
+    match instruction {
+        Instruction::Mov { src, dst } => {
+            writeln!(out, "\tmovl\t{}, {}", operand(src), operand(dst))
+        }
+        Instruction::Unary { op, operand: dst } => {
+            let mnemonic = match op {
+                UnaryOp::Neg => "negl",
+                UnaryOp::Not => "notl",
+            };
+            writeln!(out, "\t{mnemonic}\t{}", operand(dst))
+        }
+        Instruction::Binary {
+            op: op @ (BinaryOp::Sal | BinaryOp::Sar),
+            src: Operand::Reg(Reg::CX),
+            dst,
+        } => {
+            // Shift counts in a register are always a single byte
+            let mnemonic = if *op == BinaryOp::Sal { "sall" } else { "sarl" };
+            writeln!(out, "\t{mnemonic}\t%cl, {}", operand(dst))
+        }
+        Instruction::Binary { op, src, dst } => {
+            let mnemonic = match op {
+                BinaryOp::Add => "addl",
+                BinaryOp::Sub => "subl",
+                BinaryOp::Mult => "imull",
+                BinaryOp::And => "andl",
+                BinaryOp::Or => "orl",
+                BinaryOp::Xor => "xorl",
+                BinaryOp::Sal => "sall",
+                BinaryOp::Sar => "sarl",
+            };
+            writeln!(out, "\t{mnemonic}\t{}, {}", operand(src), operand(dst))
+        }
+        Instruction::Cmp { src, dst } => {
+            writeln!(out, "\tcmpl\t{}, {}", operand(src), operand(dst))
+        }
+        Instruction::Idiv(divisor) => writeln!(out, "\tidivl\t{}", operand(divisor)),
+        Instruction::Cdq => writeln!(out, "\tcdq"),
+        Instruction::Jmp(target) => writeln!(out, "\tjmp\t{}", local_label(target)),
+        Instruction::JmpCC { cond, target } => {
+            writeln!(
+                out,
+                "\tj{}\t{}",
+                condition_suffix(*cond),
+                local_label(target)
+            )
+        }
+        Instruction::SetCC { cond, operand } => {
+            writeln!(
+                out,
+                "\tset{}\t{}",
+                condition_suffix(*cond),
+                byte_operand(operand)
+            )
+        }
+        Instruction::MovZeroExtend { src, dst } => {
+            writeln!(out, "\tmovzbl\t{}, {}", byte_operand(src), operand(dst))
+        }
+        Instruction::Label(label) => writeln!(out, "{}:", local_label(label)),
+        Instruction::Push(src) => writeln!(out, "\tpushq\t{}", quad_operand(src)),
+        Instruction::Call(name) => {
+            // The function may be defined in another object, possibly a shared library
+            if cfg!(target_os = "linux") {
+                writeln!(out, "\tcall\t{}@PLT", symbol(name))
+            } else {
+                writeln!(out, "\tcall\t{}", symbol(name))
+            }
+        }
+        Instruction::AllocateStack(bytes) => writeln!(out, "\tsubq\t${bytes}, %rsp"),
+        Instruction::DeallocateStack(bytes) => writeln!(out, "\taddq\t${bytes}, %rsp"),
+        Instruction::Ret if cfi => {
+            // Code after the return is still in the frame, so it gets the frame back
+            out.push_str("\t.cfi_remember_state\n");
+            out.push_str("\tmovq\t%rbp, %rsp\n");
+            out.push_str("\tpopq\t%rbp\n");
+            out.push_str("\t.cfi_def_cfa %rsp, 8\n");
+            out.push_str("\tret\n");
+            out.push_str("\t.cfi_restore_state\n");
+            Ok(())
+        }
+        Instruction::Ret => {
+            out.push_str("\tmovq\t%rbp, %rsp\n");
+            out.push_str("\tpopq\t%rbp\n");
+            out.push_str("\tret\n");
+            Ok(())
+        }
+    }
+}
+
+fn operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Imm(value) => format!("${value}"),
+        Operand::Reg(Reg::AX) => "%eax".to_string(),
+        Operand::Reg(Reg::CX) => "%ecx".to_string(),
+        Operand::Reg(Reg::DX) => "%edx".to_string(),
+        Operand::Reg(Reg::DI) => "%edi".to_string(),
+        Operand::Reg(Reg::SI) => "%esi".to_string(),
+        Operand::Reg(Reg::R8) => "%r8d".to_string(),
+        Operand::Reg(Reg::R9) => "%r9d".to_string(),
+        Operand::Reg(Reg::R10) => "%r10d".to_string(),
+        Operand::Reg(Reg::R11) => "%r11d".to_string(),
+        Operand::Stack(offset) => format!("{offset}(%rbp)"),
+        Operand::Pseudo(name) => panic!("pseudo-register `{name}` was never replaced"),
+    }
+}
+
+/// Like [`operand`], but naming the low byte of registers
+fn byte_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Reg(Reg::AX) => "%al".to_string(),
+        Operand::Reg(Reg::CX) => "%cl".to_string(),
+        Operand::Reg(Reg::DX) => "%dl".to_string(),
+        Operand::Reg(Reg::DI) => "%dil".to_string(),
+        Operand::Reg(Reg::SI) => "%sil".to_string(),
+        Operand::Reg(Reg::R8) => "%r8b".to_string(),
+        Operand::Reg(Reg::R9) => "%r9b".to_string(),
+        Operand::Reg(Reg::R10) => "%r10b".to_string(),
+        Operand::Reg(Reg::R11) => "%r11b".to_string(),
+        operand => self::operand(operand),
+    }
+}
+
+/// Like [`operand`], but naming the whole 8 bytes of registers
+fn quad_operand(operand: &Operand) -> String {
+    match operand {
+        Operand::Reg(Reg::AX) => "%rax".to_string(),
+        Operand::Reg(Reg::CX) => "%rcx".to_string(),
+        Operand::Reg(Reg::DX) => "%rdx".to_string(),
+        Operand::Reg(Reg::DI) => "%rdi".to_string(),
+        Operand::Reg(Reg::SI) => "%rsi".to_string(),
+        Operand::Reg(Reg::R8) => "%r8".to_string(),
+        Operand::Reg(Reg::R9) => "%r9".to_string(),
+        Operand::Reg(Reg::R10) => "%r10".to_string(),
+        Operand::Reg(Reg::R11) => "%r11".to_string(),
+        operand => self::operand(operand),
+    }
+}
+
+/// Suffix of the jcc and setcc mnemonics for `cond`
+fn condition_suffix(cond: ConditionCode) -> &'static str {
+    match cond {
+        ConditionCode::E => "e",
+        ConditionCode::NE => "ne",
+        ConditionCode::G => "g",
+        ConditionCode::GE => "ge",
+        ConditionCode::L => "l",
+        ConditionCode::LE => "le",
+    }
+}
+
+/// Labels get a prefix that keeps them out of the object file's symbol table.
+fn local_label(label: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("L{label}")
+    } else {
+        format!(".L{label}")
+    }
+}
+
+/// Mach-O symbol names have a leading underscore, unlike ELF ones.
+fn symbol(name: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("_{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// Asserts that `actual` is `expected`, showing a line diff if it isn't.
+    fn assert_asm_eq(actual: &str, expected: &str) {
+        if actual != expected {
+            panic!(
+                "emitted assembly differs (-expected +actual):\n{}",
+                line_diff(expected, actual)
+            );
+        }
+    }
+
+    /// Diffs `old` against `new` line by line. Each line of the output is prefixed
+    /// with `-` if only in `old`, `+` if only in `new`, or a space if in both.
+    fn line_diff(old: &str, new: &str) -> String {
+        let old = old.lines().collect::<Vec<_>>();
+        let new = new.lines().collect::<Vec<_>>();
+
+        // common[i][j] is the length of the longest common subsequence of old[i..]
+        // and new[j..]
+        let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if old[i] == new[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut diff = String::new();
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                diff.push_str(&format!(" {}\n", old[i]));
+                i += 1;
+                j += 1;
+            } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+                diff.push_str(&format!("+{}\n", new[j]));
+                j += 1;
+            } else {
+                diff.push_str(&format!("-{}\n", old[i]));
+                i += 1;
+            }
+        }
+        diff
+    }
+
+    /// Emits a program whose only function is `main`, with the given instructions
+    fn emit_main(instructions: Vec<Instruction>) -> String {
+        emit(&AsmProgram {
+            functions: vec![AsmFunction {
+                name: "main".to_string(),
+                instructions,
+            }],
+        })
+    }
+
+    /// Surrounds `body` with the rest of what [`emit_main`] produces
+    fn main_asm(body: &str) -> String {
+        format!(
+            "\t.globl main\nmain:\n\tpushq\t%rbp\n\tmovq\t%rsp, %rbp\n{body}\n\t.section .note.GNU-stack,\"\",@progbits\n"
+        )
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(line_diff("a\nb\nc\n", "a\nb\nc\n"), " a\n b\n c\n");
+        assert_eq!(
+            line_diff("a\nb\nc\n", "a\nx\nc\nd\n"),
+            " a\n+x\n-b\n c\n+d\n"
+        );
+        assert_eq!(line_diff("a\n", ""), "-a\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "emitted assembly differs (-expected +actual):\n \tret\n-\tcdq\n")]
+    fn test_assert_asm_eq() {
+        assert_asm_eq("\tret\n", "\tret\n\tcdq\n");
+    }
+
+    #[test]
+    fn test_emit_return_constant() {
+        let program = AsmProgram {
+            functions: vec![AsmFunction {
+                name: "main".to_string(),
+                instructions: vec![
+                    Instruction::Mov {
+                        src: Operand::Imm(2),
+                        dst: Operand::Reg(Reg::AX),
+                    },
+                    Instruction::Ret,
+                ],
+            }],
+        };
+        assert_asm_eq(
+            &emit(&program),
+            concat!(
+                "\t.globl main\n",
+                "main:\n",
+                "\tpushq\t%rbp\n",
+                "\tmovq\t%rsp, %rbp\n",
+                "\tmovl\t$2, %eax\n",
+                "\tmovq\t%rbp, %rsp\n",
+                "\tpopq\t%rbp\n",
+                "\tret\n",
+                "\n",
+                "\t.section .note.GNU-stack,\"\",@progbits\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_operands() {
+        assert_asm_eq(
+            &emit_main(vec![
+                Instruction::AllocateStack(8),
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Reg::R10),
+                },
+                Instruction::Unary {
+                    op: UnaryOp::Neg,
+                    operand: Operand::Stack(-8),
+                },
+            ]),
+            &main_asm("\tsubq\t$8, %rsp\n\tmovl\t-4(%rbp), %r10d\n\tnegl\t-8(%rbp)\n"),
+        );
+    }
+
+    #[test]
+    fn test_emit_only() {
+        let function = |name: &str| AsmFunction {
+            name: name.to_string(),
+            instructions: vec![Instruction::Ret],
+        };
+        let program = AsmProgram {
+            functions: vec![function("f"), function("main"), function("g")],
+        };
+        assert_asm_eq(
+            &emit_only(&program, "main").unwrap(),
+            concat!(
+                "\t.extern f\n",
+                "\t.extern g\n",
+                "\t.globl main\n",
+                "main:\n",
+                "\tpushq\t%rbp\n",
+                "\tmovq\t%rsp, %rbp\n",
+                "\tmovq\t%rbp, %rsp\n",
+                "\tpopq\t%rbp\n",
+                "\tret\n",
+                "\n",
+                "\t.section .note.GNU-stack,\"\",@progbits\n",
+            ),
+        );
+        assert_eq!(emit_only(&program, "h"), None);
+    }
+
+    #[test]
+    fn test_emit_with_lines() {
+        let function = |name: &str| AsmFunction {
+            name: name.to_string(),
+            instructions: vec![Instruction::Ret],
+        };
+        let program = AsmProgram {
+            functions: vec![function("f"), function("main")],
+        };
+        let lines = LineInfo {
+            files: vec!["src/main.c".to_string(), "src/f.h".to_string()],
+            function_lines: HashMap::from([
+                ("f".to_string(), (2, 1)),
+                ("main".to_string(), (1, 3)),
+            ]),
+        };
+        assert_asm_eq(
+            &emit_with_lines(&program, &lines),
+            concat!(
+                "\t.file 1 \"src/main.c\"\n",
+                "\t.file 2 \"src/f.h\"\n",
+                "\t.globl f\n",
+                "f:\n",
+                "\t.cfi_startproc\n",
+                "\t.loc 2 1\n",
+                "\tpushq\t%rbp\n",
+                "\t.cfi_def_cfa_offset 16\n",
+                "\t.cfi_offset %rbp, -16\n",
+                "\tmovq\t%rsp, %rbp\n",
+                "\t.cfi_def_cfa_register %rbp\n",
+                "\t.cfi_remember_state\n",
+                "\tmovq\t%rbp, %rsp\n",
+                "\tpopq\t%rbp\n",
+                "\t.cfi_def_cfa %rsp, 8\n",
+                "\tret\n",
+                "\t.cfi_restore_state\n",
+                "\t.cfi_endproc\n",
+                "\n",
+                "\t.globl main\n",
+                "main:\n",
+                "\t.cfi_startproc\n",
+                "\t.loc 1 3\n",
+                "\tpushq\t%rbp\n",
+                "\t.cfi_def_cfa_offset 16\n",
+                "\t.cfi_offset %rbp, -16\n",
+                "\tmovq\t%rsp, %rbp\n",
+                "\t.cfi_def_cfa_register %rbp\n",
+                "\t.cfi_remember_state\n",
+                "\tmovq\t%rbp, %rsp\n",
+                "\tpopq\t%rbp\n",
+                "\t.cfi_def_cfa %rsp, 8\n",
+                "\tret\n",
+                "\t.cfi_restore_state\n",
+                "\t.cfi_endproc\n",
+                "\n",
+                "\t.section .note.GNU-stack,\"\",@progbits\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_with_cfi() {
+        let program = AsmProgram {
+            functions: vec![AsmFunction {
+                name: "main".to_string(),
+                instructions: vec![
+                    Instruction::AllocateStack(16),
+                    Instruction::Mov {
+                        src: Operand::Imm(1),
+                        dst: Operand::Reg(Reg::AX),
+                    },
+                    Instruction::Ret,
+                ],
+            }],
+        };
+        assert_asm_eq(
+            &emit_with_cfi(&program),
+            concat!(
+                "\t.globl main\n",
+                "main:\n",
+                "\t.cfi_startproc\n",
+                "\tpushq\t%rbp\n",
+                "\t.cfi_def_cfa_offset 16\n",
+                "\t.cfi_offset %rbp, -16\n",
+                "\tmovq\t%rsp, %rbp\n",
+                "\t.cfi_def_cfa_register %rbp\n",
+                "\tsubq\t$16, %rsp\n",
+                "\tmovl\t$1, %eax\n",
+                "\t.cfi_remember_state\n",
+                "\tmovq\t%rbp, %rsp\n",
+                "\tpopq\t%rbp\n",
+                "\t.cfi_def_cfa %rsp, 8\n",
+                "\tret\n",
+                "\t.cfi_restore_state\n",
+                "\t.cfi_endproc\n",
+                "\n",
+                "\t.section .note.GNU-stack,\"\",@progbits\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn test_emit_instructions() {
+        let asm = emit_main(vec![
+            Instruction::Mov {
+                src: Operand::Imm(7),
+                dst: Operand::Reg(Reg::AX),
+            },
+            Instruction::Cdq,
+            Instruction::Idiv(Operand::Reg(Reg::R10)),
+            Instruction::Mov {
+                src: Operand::Reg(Reg::DX),
+                dst: Operand::Stack(-4),
+            },
+            Instruction::Binary {
+                op: BinaryOp::Mult,
+                src: Operand::Imm(3),
+                dst: Operand::Reg(Reg::R11),
+            },
+            Instruction::Binary {
+                op: BinaryOp::Sar,
+                src: Operand::Reg(Reg::CX),
+                dst: Operand::Stack(-4),
+            },
+            Instruction::Cmp {
+                src: Operand::Imm(0),
+                dst: Operand::Stack(-4),
+            },
+            Instruction::JmpCC {
+                cond: ConditionCode::NE,
+                target: "main.or_true.0".to_string(),
+            },
+            Instruction::Jmp("main.or_end.1".to_string()),
+            Instruction::Label("main.or_true.0".to_string()),
+            Instruction::Call("f".to_string()),
+            Instruction::Cmp {
+                src: Operand::Reg(Reg::R10),
+                dst: Operand::Stack(-8),
+            },
+            Instruction::SetCC {
+                cond: ConditionCode::L,
+                operand: Operand::Stack(-12),
+            },
+            Instruction::MovZeroExtend {
+                src: Operand::Stack(-12),
+                dst: Operand::Reg(Reg::R11),
+            },
+            Instruction::SetCC {
+                cond: ConditionCode::GE,
+                operand: Operand::Reg(Reg::AX),
+            },
+            Instruction::AllocateStack(8),
+            Instruction::Mov {
+                src: Operand::Imm(1),
+                dst: Operand::Reg(Reg::DI),
+            },
+            Instruction::Mov {
+                src: Operand::Imm(2),
+                dst: Operand::Reg(Reg::SI),
+            },
+            Instruction::Push(Operand::Reg(Reg::AX)),
+            Instruction::Push(Operand::Imm(7)),
+            Instruction::DeallocateStack(24),
+        ]);
+        assert_asm_eq(
+            &asm,
+            &main_asm(concat!(
+                "\tmovl\t$7, %eax\n",
+                "\tcdq\n",
+                "\tidivl\t%r10d\n",
+                "\tmovl\t%edx, -4(%rbp)\n",
+                "\timull\t$3, %r11d\n",
+                "\tsarl\t%cl, -4(%rbp)\n",
+                "\tcmpl\t$0, -4(%rbp)\n",
+                "\tjne\t.Lmain.or_true.0\n",
+                "\tjmp\t.Lmain.or_end.1\n",
+                ".Lmain.or_true.0:\n",
+                "\tcall\tf@PLT\n",
+                "\tcmpl\t%r10d, -8(%rbp)\n",
+                "\tsetl\t-12(%rbp)\n",
+                "\tmovzbl\t-12(%rbp), %r11d\n",
+                "\tsetge\t%al\n",
+                "\tsubq\t$8, %rsp\n",
+                "\tmovl\t$1, %edi\n",
+                "\tmovl\t$2, %esi\n",
+                "\tpushq\t%rax\n",
+                "\tpushq\t$7\n",
+                "\taddq\t$24, %rsp\n",
+            )),
+        );
+    }
+}
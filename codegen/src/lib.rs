@@ -0,0 +1,994 @@
+//! Generation of x86-64 assembly from TACKY, following chapter 2 onwards of the book.
+
+use std::collections::HashMap;
+
+use errors::{DriverError, ExitCode};
+use tacky::ir::{self, TackyProgram, Val};
+
+pub mod asm;
+pub mod emit;
+
+use asm::{AsmFunction, AsmProgram, BinaryOp, ConditionCode, Instruction, Operand, Reg, UnaryOp};
+
+/// Lowers `tacky` into assembly.
+///
+/// TACKY variables become [`Operand::Pseudo`]s, which still need to be assigned
+/// stack slots before the program can be emitted.
+pub fn codegen(tacky: &TackyProgram) -> AsmProgram {
+    AsmProgram {
+        functions: tacky.functions.iter().map(function).collect(),
+    }
+}
+
+/// Registers the first integer arguments of a call are passed in, in order, by the
+/// System V calling convention
+const ARG_REGISTERS: [Reg; 6] = [Reg::DI, Reg::SI, Reg::DX, Reg::CX, Reg::R8, Reg::R9];
+
+fn function(function: &ir::Function) -> AsmFunction {
+    let mut instructions = Vec::new();
+    // Copy the parameters out of where the caller put them, so they can be treated
+    // like any other variable
+    for (i, param) in function.params.iter().enumerate() {
+        let src = match ARG_REGISTERS.get(i) {
+            Some(reg) => Operand::Reg(*reg),
+            // Above the saved %rbp and the return address, in order
+            None => Operand::Stack(16 + 8 * (i - ARG_REGISTERS.len()) as i32),
+        };
+        instructions.push(Instruction::Mov {
+            src,
+            dst: Operand::Pseudo(param.clone()),
+        });
+    }
+    for instruction in &function.body {
+        lower_instruction(instruction, &mut instructions);
+    }
+    AsmFunction {
+        name: function.name.clone(),
+        instructions,
+    }
+}
+
+fn lower_instruction(instruction: &ir::Instruction, out: &mut Vec<Instruction>) {
+    match instruction {
+        ir::Instruction::Return(val) => {
+            out.push(Instruction::Mov {
+                src: operand(val),
+                dst: Operand::Reg(Reg::AX),
+            });
+            out.push(Instruction::Ret);
+        }
+        ir::Instruction::Unary { op, src, dst } => {
+            let op = match op {
+                ir::UnaryOp::Negate => UnaryOp::Neg,
+                ir::UnaryOp::Complement => UnaryOp::Not,
+                ir::UnaryOp::Not => {
+                    // `!x` is `x == 0`
+                    lower_comparison(ConditionCode::E, src, &Val::Constant(0), dst, out);
+                    return;
+                }
+            };
+            out.push(Instruction::Mov {
+                src: operand(src),
+                dst: operand(dst),
+            });
+            out.push(Instruction::Unary {
+                op,
+                operand: operand(dst),
+            });
+        }
+        ir::Instruction::Copy { src, dst } => out.push(Instruction::Mov {
+            src: operand(src),
+            dst: operand(dst),
+        }),
+        ir::Instruction::Binary {
+            op: op @ (ir::BinaryOp::Divide | ir::BinaryOp::Remainder),
+            src1,
+            src2,
+            dst,
+        } => {
+            let result = if *op == ir::BinaryOp::Divide {
+                Reg::AX
+            } else {
+                Reg::DX
+            };
+            out.push(Instruction::Mov {
+                src: operand(src1),
+                dst: Operand::Reg(Reg::AX),
+            });
+            out.push(Instruction::Cdq);
+            out.push(Instruction::Idiv(operand(src2)));
+            out.push(Instruction::Mov {
+                src: Operand::Reg(result),
+                dst: operand(dst),
+            });
+        }
+        ir::Instruction::Binary {
+            op:
+                op @ (ir::BinaryOp::Equal
+                | ir::BinaryOp::NotEqual
+                | ir::BinaryOp::LessThan
+                | ir::BinaryOp::LessOrEqual
+                | ir::BinaryOp::GreaterThan
+                | ir::BinaryOp::GreaterOrEqual),
+            src1,
+            src2,
+            dst,
+        } => {
+            let cond = match op {
+                ir::BinaryOp::Equal => ConditionCode::E,
+                ir::BinaryOp::NotEqual => ConditionCode::NE,
+                ir::BinaryOp::LessThan => ConditionCode::L,
+                ir::BinaryOp::LessOrEqual => ConditionCode::LE,
+                ir::BinaryOp::GreaterThan => ConditionCode::G,
+                ir::BinaryOp::GreaterOrEqual => ConditionCode::GE,
+                _ => unreachable!(),
+            };
+            lower_comparison(cond, src1, src2, dst, out);
+        }
+        ir::Instruction::Binary {
+            op,
+            src1,
+            src2,
+            dst,
+        } => {
+            let op = match op {
+                ir::BinaryOp::Add => BinaryOp::Add,
+                ir::BinaryOp::Subtract => BinaryOp::Sub,
+                ir::BinaryOp::Multiply => BinaryOp::Mult,
+                ir::BinaryOp::BitwiseAnd => BinaryOp::And,
+                ir::BinaryOp::BitwiseOr => BinaryOp::Or,
+                ir::BinaryOp::BitwiseXor => BinaryOp::Xor,
+                ir::BinaryOp::LeftShift => BinaryOp::Sal,
+                ir::BinaryOp::RightShift => BinaryOp::Sar,
+                ir::BinaryOp::Divide
+                | ir::BinaryOp::Remainder
+                | ir::BinaryOp::Equal
+                | ir::BinaryOp::NotEqual
+                | ir::BinaryOp::LessThan
+                | ir::BinaryOp::LessOrEqual
+                | ir::BinaryOp::GreaterThan
+                | ir::BinaryOp::GreaterOrEqual => unreachable!(),
+            };
+            out.push(Instruction::Mov {
+                src: operand(src1),
+                dst: operand(dst),
+            });
+            out.push(Instruction::Binary {
+                op,
+                src: operand(src2),
+                dst: operand(dst),
+            });
+        }
+        ir::Instruction::Jump(target) => out.push(Instruction::Jmp(target.clone())),
+        ir::Instruction::JumpIfZero { condition, target } => {
+            out.push(Instruction::Cmp {
+                src: Operand::Imm(0),
+                dst: operand(condition),
+            });
+            out.push(Instruction::JmpCC {
+                cond: ConditionCode::E,
+                target: target.clone(),
+            });
+        }
+        ir::Instruction::JumpIfNotZero { condition, target } => {
+            out.push(Instruction::Cmp {
+                src: Operand::Imm(0),
+                dst: operand(condition),
+            });
+            out.push(Instruction::JmpCC {
+                cond: ConditionCode::NE,
+                target: target.clone(),
+            });
+        }
+        ir::Instruction::Label(label) => out.push(Instruction::Label(label.clone())),
+        ir::Instruction::FunCall { name, args, dst } => lower_call(name, args, dst, out),
+    }
+}
+
+/// Lowers a call following the System V calling convention.
+///
+/// Every variable lives on the stack, and not in a caller-saved register, so nothing
+/// needs saving around the call.
+fn lower_call(name: &str, args: &[Val], dst: &Val, out: &mut Vec<Instruction>) {
+    let split = args.len().min(ARG_REGISTERS.len());
+    let (register_args, stack_args) = args.split_at(split);
+
+    // The stack must be 16-byte aligned at the call, and each stack argument takes
+    // 8 bytes
+    let padding = if stack_args.len() % 2 == 1 { 8 } else { 0 };
+    if padding != 0 {
+        out.push(Instruction::AllocateStack(padding));
+    }
+
+    for (arg, reg) in register_args.iter().zip(ARG_REGISTERS) {
+        out.push(Instruction::Mov {
+            src: operand(arg),
+            dst: Operand::Reg(reg),
+        });
+    }
+    // The last argument is pushed first, so the first ends up nearest the top
+    for arg in stack_args.iter().rev() {
+        match operand(arg) {
+            arg @ Operand::Imm(_) => out.push(Instruction::Push(arg)),
+            // Pushing 8 bytes from a 4-byte stack slot could read past it, so copy it
+            // into a register first
+            arg => {
+                out.push(Instruction::Mov {
+                    src: arg,
+                    dst: Operand::Reg(Reg::AX),
+                });
+                out.push(Instruction::Push(Operand::Reg(Reg::AX)));
+            }
+        }
+    }
+
+    out.push(Instruction::Call(name.to_string()));
+
+    let bytes_to_remove = 8 * stack_args.len() as i32 + padding;
+    if bytes_to_remove != 0 {
+        out.push(Instruction::DeallocateStack(bytes_to_remove));
+    }
+    out.push(Instruction::Mov {
+        src: Operand::Reg(Reg::AX),
+        dst: operand(dst),
+    });
+}
+
+/// Lowers `dst = src1 <cond> src2`, where the result is 1 if the comparison holds,
+/// and otherwise 0.
+fn lower_comparison(
+    cond: ConditionCode,
+    src1: &Val,
+    src2: &Val,
+    dst: &Val,
+    out: &mut Vec<Instruction>,
+) {
+    out.push(Instruction::Cmp {
+        src: operand(src2),
+        dst: operand(src1),
+    });
+    // setcc only writes a byte, so the rest of the result is cleared afterwards
+    out.push(Instruction::SetCC {
+        cond,
+        operand: operand(dst),
+    });
+    out.push(Instruction::MovZeroExtend {
+        src: operand(dst),
+        dst: operand(dst),
+    });
+}
+
+fn operand(val: &Val) -> Operand {
+    match val {
+        Val::Constant(value) => Operand::Imm(*value),
+        Val::Var(name) => Operand::Pseudo(name.clone()),
+    }
+}
+
+/// Size in bytes of the stack slot given to each pseudo-register
+const SLOT_SIZE: i64 = 4;
+
+/// A function needs more stack than can be addressed with 32-bit offsets
+#[derive(Debug, PartialEq)]
+pub struct FrameTooLarge {
+    /// Name of the function
+    pub function: String,
+}
+
+impl From<FrameTooLarge> for DriverError {
+    fn from(e: FrameTooLarge) -> Self {
+        Self {
+            code: ExitCode::DataErr,
+            msg: format!("fatal: the stack frame of `{}` is too large.", e.function),
+            ..Default::default()
+        }
+    }
+}
+
+/// Assigns every distinct pseudo-register in `func` its own stack slot, and
+/// rewrites its uses into [`Operand::Stack`]s.
+///
+/// Slots are allocated downwards from `%rbp` in order of first use. Returns the
+/// size of the stack frame needed for all the slots, rounded up to a multiple of
+/// 16 so that the stack stays aligned.
+pub fn replace_pseudos(func: &mut AsmFunction) -> Result<i32, FrameTooLarge> {
+    let too_large = || FrameTooLarge {
+        function: func.name.clone(),
+    };
+
+    // Offsets are computed in 64 bits, so that they can be range checked rather
+    // than wrapping
+    let mut offsets = HashMap::new();
+    for instruction in &mut func.instructions {
+        for operand in instruction.operands_mut() {
+            if let Operand::Pseudo(name) = operand {
+                let next_offset = -SLOT_SIZE * (offsets.len() as i64 + 1);
+                let offset = *offsets.entry(name.clone()).or_insert(next_offset);
+                *operand = Operand::Stack(i32::try_from(offset).map_err(|_| too_large())?);
+            }
+        }
+    }
+    frame_size(SLOT_SIZE * offsets.len() as i64).ok_or_else(too_large)
+}
+
+/// Rounds `bytes` up to a multiple of 16, or returns `None` if the result doesn't
+/// fit in an `i32`.
+fn frame_size(bytes: i64) -> Option<i32> {
+    let rounded = bytes.checked_add(15)? / 16 * 16;
+    i32::try_from(rounded).ok()
+}
+
+/// Reserves `stack_size` bytes of stack at the start of `func`, and rewrites
+/// instructions whose operands x86 doesn't allow together.
+///
+/// Should run after [`replace_pseudos`], which is what makes operands memory
+/// addresses. `%r10d` and `%r11d` are used as scratch registers.
+pub fn fixup_instructions(func: &mut AsmFunction, stack_size: i32) {
+    let mut instructions = Vec::with_capacity(func.instructions.len() + 1);
+    instructions.push(Instruction::AllocateStack(stack_size));
+    for instruction in func.instructions.drain(..) {
+        match instruction {
+            // At most one operand of a mov may be in memory
+            Instruction::Mov {
+                src: src @ Operand::Stack(_),
+                dst: dst @ Operand::Stack(_),
+            } => {
+                instructions.push(Instruction::Mov {
+                    src,
+                    dst: Operand::Reg(Reg::R10),
+                });
+                instructions.push(Instruction::Mov {
+                    src: Operand::Reg(Reg::R10),
+                    dst,
+                });
+            }
+            // Likewise for the other two-operand instructions, except for imul and the
+            // shifts, which have stricter rules below
+            Instruction::Binary {
+                op:
+                    op @ (BinaryOp::Add | BinaryOp::Sub | BinaryOp::And | BinaryOp::Or | BinaryOp::Xor),
+                src: src @ Operand::Stack(_),
+                dst: dst @ Operand::Stack(_),
+            } => {
+                instructions.push(Instruction::Mov {
+                    src,
+                    dst: Operand::Reg(Reg::R10),
+                });
+                instructions.push(Instruction::Binary {
+                    op,
+                    src: Operand::Reg(Reg::R10),
+                    dst,
+                });
+            }
+            // The destination of imul must be a register
+            Instruction::Binary {
+                op: BinaryOp::Mult,
+                src,
+                dst: dst @ Operand::Stack(_),
+            } => {
+                instructions.push(Instruction::Mov {
+                    src: dst.clone(),
+                    dst: Operand::Reg(Reg::R11),
+                });
+                instructions.push(Instruction::Binary {
+                    op: BinaryOp::Mult,
+                    src,
+                    dst: Operand::Reg(Reg::R11),
+                });
+                instructions.push(Instruction::Mov {
+                    src: Operand::Reg(Reg::R11),
+                    dst,
+                });
+            }
+            // A shift count is either an immediate or in %cl
+            Instruction::Binary {
+                op: op @ (BinaryOp::Sal | BinaryOp::Sar),
+                src: src @ (Operand::Stack(_) | Operand::Reg(_)),
+                dst,
+            } if src != Operand::Reg(Reg::CX) => {
+                instructions.push(Instruction::Mov {
+                    src,
+                    dst: Operand::Reg(Reg::CX),
+                });
+                instructions.push(Instruction::Binary {
+                    op,
+                    src: Operand::Reg(Reg::CX),
+                    dst,
+                });
+            }
+            // At most one operand of a cmp may be in memory, and the second can't be an
+            // immediate
+            Instruction::Cmp {
+                src: src @ Operand::Stack(_),
+                dst: dst @ Operand::Stack(_),
+            } => {
+                instructions.push(Instruction::Mov {
+                    src,
+                    dst: Operand::Reg(Reg::R10),
+                });
+                instructions.push(Instruction::Cmp {
+                    src: Operand::Reg(Reg::R10),
+                    dst,
+                });
+            }
+            Instruction::Cmp {
+                src,
+                dst: dst @ Operand::Imm(_),
+            } => {
+                instructions.push(Instruction::Mov {
+                    src: dst,
+                    dst: Operand::Reg(Reg::R11),
+                });
+                instructions.push(Instruction::Cmp {
+                    src,
+                    dst: Operand::Reg(Reg::R11),
+                });
+            }
+            // The destination of movzb must be a register
+            Instruction::MovZeroExtend {
+                src,
+                dst: dst @ Operand::Stack(_),
+            } => {
+                instructions.push(Instruction::MovZeroExtend {
+                    src,
+                    dst: Operand::Reg(Reg::R11),
+                });
+                instructions.push(Instruction::Mov {
+                    src: Operand::Reg(Reg::R11),
+                    dst,
+                });
+            }
+            // idiv can't take an immediate
+            Instruction::Idiv(src @ Operand::Imm(_)) => {
+                instructions.push(Instruction::Mov {
+                    src,
+                    dst: Operand::Reg(Reg::R10),
+                });
+                instructions.push(Instruction::Idiv(Operand::Reg(Reg::R10)));
+            }
+            instruction => instructions.push(instruction),
+        }
+    }
+    func.instructions = instructions;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn main_function(body: Vec<ir::Instruction>) -> TackyProgram {
+        TackyProgram {
+            functions: vec![ir::Function {
+                name: "main".to_string(),
+                params: vec![],
+                body,
+            }],
+        }
+    }
+
+    fn pseudo(name: &str) -> Operand {
+        Operand::Pseudo(name.to_string())
+    }
+
+    #[test]
+    fn test_codegen_return_constant() {
+        assert_eq!(
+            codegen(&main_function(vec![ir::Instruction::Return(
+                Val::Constant(2)
+            )])),
+            AsmProgram {
+                functions: vec![AsmFunction {
+                    name: "main".to_string(),
+                    instructions: vec![
+                        Instruction::Mov {
+                            src: Operand::Imm(2),
+                            dst: Operand::Reg(Reg::AX),
+                        },
+                        Instruction::Ret,
+                    ],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_codegen_unary() {
+        let program = codegen(&main_function(vec![
+            ir::Instruction::Unary {
+                op: ir::UnaryOp::Complement,
+                src: Val::Constant(5),
+                dst: Val::Var("tmp.0".to_string()),
+            },
+            ir::Instruction::Return(Val::Var("tmp.0".to_string())),
+        ]));
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![
+                Instruction::Mov {
+                    src: Operand::Imm(5),
+                    dst: pseudo("tmp.0"),
+                },
+                Instruction::Unary {
+                    op: UnaryOp::Not,
+                    operand: pseudo("tmp.0"),
+                },
+                Instruction::Mov {
+                    src: pseudo("tmp.0"),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Ret,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_call() {
+        let program = codegen(&main_function(vec![ir::Instruction::FunCall {
+            name: "f".to_string(),
+            args: vec![],
+            dst: Val::Var("tmp.0".to_string()),
+        }]));
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![
+                Instruction::Call("f".to_string()),
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::AX),
+                    dst: pseudo("tmp.0"),
+                },
+            ]
+        );
+    }
+
+    /// A call to `f` with the constants `0..count` as arguments
+    fn call_with_args(count: i64) -> Vec<Instruction> {
+        let program = codegen(&main_function(vec![ir::Instruction::FunCall {
+            name: "f".to_string(),
+            args: (0..count).map(Val::Constant).collect(),
+            dst: Val::Var("tmp.0".to_string()),
+        }]));
+        program.functions[0].instructions.clone()
+    }
+
+    #[test]
+    fn test_codegen_call_args() {
+        assert_eq!(
+            call_with_args(2),
+            vec![
+                Instruction::Mov {
+                    src: Operand::Imm(0),
+                    dst: Operand::Reg(Reg::DI),
+                },
+                Instruction::Mov {
+                    src: Operand::Imm(1),
+                    dst: Operand::Reg(Reg::SI),
+                },
+                Instruction::Call("f".to_string()),
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::AX),
+                    dst: pseudo("tmp.0"),
+                },
+            ]
+        );
+
+        // The seventh argument is pushed, after padding to keep the stack aligned
+        let instructions = call_with_args(7);
+        assert_eq!(instructions[0], Instruction::AllocateStack(8));
+        assert_eq!(
+            instructions[6..],
+            [
+                Instruction::Mov {
+                    src: Operand::Imm(5),
+                    dst: Operand::Reg(Reg::R9),
+                },
+                Instruction::Push(Operand::Imm(6)),
+                Instruction::Call("f".to_string()),
+                Instruction::DeallocateStack(16),
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::AX),
+                    dst: pseudo("tmp.0"),
+                },
+            ]
+        );
+
+        // An even number of stack arguments needs no padding
+        let instructions = call_with_args(8);
+        assert_eq!(
+            instructions[6..9],
+            [
+                Instruction::Push(Operand::Imm(7)),
+                Instruction::Push(Operand::Imm(6)),
+                Instruction::Call("f".to_string()),
+            ]
+        );
+        assert_eq!(instructions[9], Instruction::DeallocateStack(16));
+    }
+
+    #[test]
+    fn test_codegen_params() {
+        let program = codegen(&TackyProgram {
+            functions: vec![ir::Function {
+                name: "f".to_string(),
+                params: (0..8).map(|i| format!("p{i}")).collect(),
+                body: vec![ir::Instruction::Return(Val::Var("p7".to_string()))],
+            }],
+        });
+        let instructions = &program.functions[0].instructions;
+        assert_eq!(
+            instructions[0],
+            Instruction::Mov {
+                src: Operand::Reg(Reg::DI),
+                dst: pseudo("p0"),
+            }
+        );
+        assert_eq!(
+            instructions[6..8],
+            [
+                Instruction::Mov {
+                    src: Operand::Stack(16),
+                    dst: pseudo("p6"),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(24),
+                    dst: pseudo("p7"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_jumps() {
+        let program = codegen(&main_function(vec![
+            ir::Instruction::JumpIfZero {
+                condition: Val::Var("x".to_string()),
+                target: "main.and_false.0".to_string(),
+            },
+            ir::Instruction::JumpIfNotZero {
+                condition: Val::Constant(1),
+                target: "main.or_true.1".to_string(),
+            },
+            ir::Instruction::Jump("main.and_end.2".to_string()),
+            ir::Instruction::Label("main.and_false.0".to_string()),
+        ]));
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![
+                Instruction::Cmp {
+                    src: Operand::Imm(0),
+                    dst: pseudo("x"),
+                },
+                Instruction::JmpCC {
+                    cond: ConditionCode::E,
+                    target: "main.and_false.0".to_string(),
+                },
+                Instruction::Cmp {
+                    src: Operand::Imm(0),
+                    dst: Operand::Imm(1),
+                },
+                Instruction::JmpCC {
+                    cond: ConditionCode::NE,
+                    target: "main.or_true.1".to_string(),
+                },
+                Instruction::Jmp("main.and_end.2".to_string()),
+                Instruction::Label("main.and_false.0".to_string()),
+            ]
+        );
+
+        // Comparing against an immediate needs a scratch register
+        let mut function = program.functions[0].clone();
+        fixup_instructions(&mut function, 0);
+        assert_eq!(
+            function.instructions[3..5],
+            [
+                Instruction::Mov {
+                    src: Operand::Imm(1),
+                    dst: Operand::Reg(Reg::R11),
+                },
+                Instruction::Cmp {
+                    src: Operand::Imm(0),
+                    dst: Operand::Reg(Reg::R11),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_comparison() {
+        let program = codegen(&main_function(vec![
+            ir::Instruction::Binary {
+                op: ir::BinaryOp::LessThan,
+                src1: Val::Var("a".to_string()),
+                src2: Val::Var("b".to_string()),
+                dst: Val::Var("tmp.0".to_string()),
+            },
+            ir::Instruction::Unary {
+                op: ir::UnaryOp::Not,
+                src: Val::Var("tmp.0".to_string()),
+                dst: Val::Var("tmp.1".to_string()),
+            },
+        ]));
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![
+                // Flags are set from `a - b`
+                Instruction::Cmp {
+                    src: pseudo("b"),
+                    dst: pseudo("a"),
+                },
+                Instruction::SetCC {
+                    cond: ConditionCode::L,
+                    operand: pseudo("tmp.0"),
+                },
+                Instruction::MovZeroExtend {
+                    src: pseudo("tmp.0"),
+                    dst: pseudo("tmp.0"),
+                },
+                Instruction::Cmp {
+                    src: Operand::Imm(0),
+                    dst: pseudo("tmp.0"),
+                },
+                Instruction::SetCC {
+                    cond: ConditionCode::E,
+                    operand: pseudo("tmp.1"),
+                },
+                Instruction::MovZeroExtend {
+                    src: pseudo("tmp.1"),
+                    dst: pseudo("tmp.1"),
+                },
+            ]
+        );
+
+        // movzb can't write to memory
+        let mut function = program.functions[0].clone();
+        replace_pseudos(&mut function).unwrap();
+        fixup_instructions(&mut function, 16);
+        assert_eq!(
+            function.instructions[1..5],
+            [
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Reg::R10),
+                },
+                Instruction::Cmp {
+                    src: Operand::Reg(Reg::R10),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::SetCC {
+                    cond: ConditionCode::L,
+                    operand: Operand::Stack(-12),
+                },
+                Instruction::MovZeroExtend {
+                    src: Operand::Stack(-12),
+                    dst: Operand::Reg(Reg::R11),
+                },
+            ]
+        );
+        assert_eq!(
+            function.instructions[5],
+            Instruction::Mov {
+                src: Operand::Reg(Reg::R11),
+                dst: Operand::Stack(-12),
+            }
+        );
+    }
+
+    #[test]
+    fn test_replace_pseudos() {
+        let mut function = AsmFunction {
+            name: "main".to_string(),
+            instructions: vec![
+                Instruction::Mov {
+                    src: Operand::Imm(5),
+                    dst: pseudo("a"),
+                },
+                Instruction::Mov {
+                    src: pseudo("a"),
+                    dst: pseudo("b"),
+                },
+                Instruction::Unary {
+                    op: UnaryOp::Neg,
+                    operand: pseudo("b"),
+                },
+                Instruction::Mov {
+                    src: pseudo("b"),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Ret,
+            ],
+        };
+        assert_eq!(replace_pseudos(&mut function), Ok(16));
+        assert_eq!(
+            function.instructions,
+            vec![
+                Instruction::Mov {
+                    src: Operand::Imm(5),
+                    dst: Operand::Stack(-4),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Unary {
+                    op: UnaryOp::Neg,
+                    operand: Operand::Stack(-8),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-8),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Ret,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixup_instructions() {
+        let mut function = AsmFunction {
+            name: "main".to_string(),
+            instructions: vec![
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-8),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Ret,
+            ],
+        };
+        fixup_instructions(&mut function, 8);
+        assert_eq!(
+            function.instructions,
+            vec![
+                Instruction::AllocateStack(8),
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Reg::R10),
+                },
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::R10),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-8),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Ret,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codegen_binary() {
+        let program = codegen(&main_function(vec![
+            ir::Instruction::Binary {
+                op: ir::BinaryOp::Remainder,
+                src1: Val::Constant(7),
+                src2: Val::Constant(3),
+                dst: Val::Var("tmp.0".to_string()),
+            },
+            ir::Instruction::Binary {
+                op: ir::BinaryOp::Subtract,
+                src1: Val::Var("tmp.0".to_string()),
+                src2: Val::Constant(1),
+                dst: Val::Var("tmp.1".to_string()),
+            },
+        ]));
+        assert_eq!(
+            program.functions[0].instructions,
+            vec![
+                Instruction::Mov {
+                    src: Operand::Imm(7),
+                    dst: Operand::Reg(Reg::AX),
+                },
+                Instruction::Cdq,
+                Instruction::Idiv(Operand::Imm(3)),
+                // The remainder is left in %edx
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::DX),
+                    dst: pseudo("tmp.0"),
+                },
+                Instruction::Mov {
+                    src: pseudo("tmp.0"),
+                    dst: pseudo("tmp.1"),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Sub,
+                    src: Operand::Imm(1),
+                    dst: pseudo("tmp.1"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fixup_binary() {
+        let mut function = AsmFunction {
+            name: "main".to_string(),
+            instructions: vec![
+                Instruction::Idiv(Operand::Imm(3)),
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src: Operand::Stack(-4),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Mult,
+                    src: Operand::Imm(3),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Sal,
+                    src: Operand::Stack(-4),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Sar,
+                    src: Operand::Imm(2),
+                    dst: Operand::Stack(-8),
+                },
+            ],
+        };
+        fixup_instructions(&mut function, 8);
+        assert_eq!(
+            function.instructions,
+            vec![
+                Instruction::AllocateStack(8),
+                Instruction::Mov {
+                    src: Operand::Imm(3),
+                    dst: Operand::Reg(Reg::R10),
+                },
+                Instruction::Idiv(Operand::Reg(Reg::R10)),
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Reg::R10),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src: Operand::Reg(Reg::R10),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-8),
+                    dst: Operand::Reg(Reg::R11),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Mult,
+                    src: Operand::Imm(3),
+                    dst: Operand::Reg(Reg::R11),
+                },
+                Instruction::Mov {
+                    src: Operand::Reg(Reg::R11),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Mov {
+                    src: Operand::Stack(-4),
+                    dst: Operand::Reg(Reg::CX),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Sal,
+                    src: Operand::Reg(Reg::CX),
+                    dst: Operand::Stack(-8),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Sar,
+                    src: Operand::Imm(2),
+                    dst: Operand::Stack(-8),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frame_size() {
+        assert_eq!(frame_size(0), Some(0));
+        assert_eq!(frame_size(4), Some(16));
+        assert_eq!(frame_size(32), Some(32));
+        assert_eq!(frame_size(1_200_004), Some(1_200_016));
+        assert_eq!(frame_size(i32::MAX as i64 - 15), Some(i32::MAX - 15));
+        // Rounding up would no longer fit
+        assert_eq!(frame_size(i32::MAX as i64 - 14), None);
+        assert_eq!(frame_size(i64::MAX), None);
+    }
+}
@@ -0,0 +1,2154 @@
+//! The compiler pipeline, from preprocessing a C source file to linking it, for
+//! the command line driver and anything else that wants to embed the compiler.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Read};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use errors::{DriverError, ExitCode};
+use parser::ast::{BlockItem, Declaration, Program, Statement};
+use semantics::typecheck::{SymbolInfo, SymbolTable};
+
+/// What to compile, and how far.
+///
+/// Every option is off by default, so a literal only needs the ones it sets:
+///
+/// ```no_run
+/// let options = compiler::CompileOptions {
+///     source_paths: vec!["main.c".to_string()],
+///     ..Default::default()
+/// };
+/// compiler::compile(&options).unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CompileOptions {
+    /// Paths to C source files to compile and link together, or `-` to read one
+    /// from stdin. Files ending in `.i` are taken to be preprocessed already.
+    pub source_paths: Vec<String>,
+    /// Preprocess, print the preprocessed source (or write it to `output`), then stop
+    pub preprocess_only: bool,
+    /// Run the lexer, but stop before parsing
+    pub lex: bool,
+    /// Run the lexer and parser, but stop before assembly generation
+    pub parse: bool,
+    /// Run the lexer, parser, and TACKY generation, print the TACKY, then stop
+    pub tacky: bool,
+    /// Perform lexing, parsing, and assembly generation, but stop before code emission
+    pub codegen: bool,
+    /// Emit assembly, but stop before assembling and linking
+    pub assembly: bool,
+    /// Assemble into an object file, but stop before linking
+    pub compile_only: bool,
+    /// Warn about identifiers longer than this many characters
+    pub warn_identifier_length: Option<usize>,
+    /// Fail on identifiers longer than this many characters [default: 1024]
+    pub max_identifier_length: Option<usize>,
+    /// Replace trigraph sequences before preprocessing, so that they can spell
+    /// directives and line continuations
+    pub trigraphs: bool,
+    /// Run the lexer, print each token with its line and column, then stop
+    pub dump_tokens: bool,
+    /// Run the lexer and parser, print the AST as JSON, then stop
+    pub dump_ast_json: bool,
+    /// Where to write the executable, or with `assembly` or `compile_only` the
+    /// assembly or object file. `-` with `assembly` means stdout.
+    pub output: Option<String>,
+    /// Link into a shared library at this path, instead of an executable
+    pub emit_shared: Option<String>,
+    /// Libraries to link against
+    pub libraries: Vec<String>,
+    /// Directories to search for libraries
+    pub library_paths: Vec<String>,
+    /// Directories to search for headers
+    pub include_dirs: Vec<String>,
+    /// Macros to define for the preprocessor, as `NAME[=VALUE]`
+    pub defines: Vec<String>,
+    /// Write the generated assembly to this path, instead of next to the source file
+    pub asm_out: Option<String>,
+    /// Only emit assembly for the named function, then stop
+    pub only_function: Option<String>,
+    /// Fail if any function needs more than this many bytes of stack
+    /// [default: 1048576]
+    pub max_stack_bytes: Option<u32>,
+    /// C compiler to preprocess, assemble, and link with [default: gcc]
+    pub cc: Option<String>,
+    /// Keep the object file of every source next to it, as well as its
+    /// preprocessed source and assembly
+    pub save_temps: bool,
+    /// Emit `.file` and `.loc` directives, so a debugger can show the line each
+    /// function starts on, in the source file it was defined in. Implies
+    /// `emit_cfi`.
+    pub debug_info: bool,
+    /// Emit CFI directives describing the stack frame of each function, so
+    /// debuggers and unwinders can walk the stack
+    pub emit_cfi: bool,
+    /// Print the commands run to preprocess, assemble, and link
+    pub verbose: bool,
+    /// Print the steps that would be taken, without running any of them
+    pub dry_run: bool,
+    /// Write a listing of the tokens, AST, TACKY, and assembly to this path
+    pub listing: Option<String>,
+    /// Warn if `main` returns a constant that doesn't fit in an exit code
+    pub warn_truncated_exit_codes: bool,
+}
+
+/// Default for [`CompileOptions::max_stack_bytes`]
+const DEFAULT_MAX_STACK_BYTES: u32 = 1 << 20;
+
+/// Default for [`CompileOptions::cc`]
+const DEFAULT_CC: &str = "gcc";
+
+/// Source path meaning the source is read from stdin
+const STDIN_PATH: &str = "-";
+
+/// Default executable path when the first source is read from stdin, like gcc
+const STDIN_OUTPUT: &str = "a.out";
+
+/// Extension of source files that still need preprocessing
+const SOURCE_EXTENSION: &str = "c";
+
+/// Extension of source files that have already been preprocessed, like gcc
+const PREPROCESSED_EXTENSION: &str = "i";
+
+/// A copy of stdin, saved to a temporary source file so it can be preprocessed.
+/// The file and its intermediate files are removed on drop.
+struct StdinSource {
+    path: PathBuf,
+}
+
+impl StdinSource {
+    fn read() -> Result<Self, DriverError> {
+        let path = stdin_path();
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        fs::write(&path, source)?;
+        Ok(Self { path })
+    }
+}
+
+/// Path that stdin is saved to
+fn stdin_path() -> PathBuf {
+    std::env::temp_dir().join(format!("driver_stdin_{}.c", std::process::id()))
+}
+
+impl Drop for StdinSource {
+    fn drop(&mut self) {
+        let intermediate_path = intermediate_path(&self.path);
+        for extension in ["c", "i", "s", "o"] {
+            // The intermediate files may not have been written
+            let _ = fs::remove_file(intermediate_path.with_extension(extension));
+        }
+    }
+}
+
+/// Compiles the program described by `options`, stopping early if they ask to.
+///
+/// Progress messages are printed to stdout, or to stderr when stdout is taken by
+/// the output.
+pub fn compile(options: &CompileOptions) -> Result<(), DriverError> {
+    let multiple_sources = options.source_paths.len() > 1;
+    if multiple_sources
+        && (options.asm_out.is_some()
+            || options.only_function.is_some()
+            || options.listing.is_some()
+            || ((options.compile_only || options.assembly || options.preprocess_only)
+                && options.output.is_some()))
+    {
+        return Err(DriverError {
+            code: ExitCode::Usage,
+            msg:
+                "fatal: --asm-out, --only-function, --listing, and -o with -c, -S, or -E need a single source file."
+                    .to_string(),
+            ..Default::default()
+        });
+    }
+
+    let stdin_count = options
+        .source_paths
+        .iter()
+        .filter(|source_path| *source_path == STDIN_PATH)
+        .count();
+    if stdin_count > 1 {
+        return Err(DriverError {
+            code: ExitCode::Usage,
+            msg: "fatal: stdin (`-`) can only be read once.".to_string(),
+            ..Default::default()
+        });
+    }
+    // Intermediate files of stdin are temporary, so these outputs need a path
+    if stdin_count > 0 && (options.compile_only || options.assembly) && options.output.is_none() {
+        return Err(DriverError {
+            code: ExitCode::Usage,
+            msg: "fatal: -c and -S need -o when reading from stdin.".to_string(),
+            ..Default::default()
+        });
+    }
+
+    // Check every file before compiling any of them
+    if let Some(source_path) = options.source_paths.iter().find(|source_path| {
+        *source_path != STDIN_PATH
+            && !matches!(
+                Path::new(source_path).extension().and_then(OsStr::to_str),
+                Some(SOURCE_EXTENSION | PREPROCESSED_EXTENSION)
+            )
+    }) {
+        return Err(DriverError {
+            code: ExitCode::Usage,
+            msg: format!("fatal: \"{source_path}\" is not a C source file (expected .c or .i)."),
+            ..Default::default()
+        });
+    }
+
+    if options.dry_run {
+        print!("{}", plan(options));
+        return Ok(());
+    }
+
+    let stdin_source = if stdin_count > 0 {
+        Some(StdinSource::read()?)
+    } else {
+        None
+    };
+
+    let mut link_paths = Vec::new();
+    let mut symbol_tables = Vec::new();
+    for source_path in &options.source_paths {
+        let compiled_path = match &stdin_source {
+            Some(stdin_source) if source_path == STDIN_PATH => stdin_source.path.to_str(),
+            _ => Some(source_path.as_str()),
+        };
+        // Unwrap is safe, as the temporary directory is only ever given as a string
+        let compiled = compile_source(compiled_path.unwrap(), options).map_err(|e| {
+            if multiple_sources {
+                DriverError {
+                    msg: format!("{source_path}: {}", e.msg),
+                    ..e
+                }
+            } else {
+                e
+            }
+        })?;
+        if let Some((link_path, symbols)) = compiled {
+            link_paths.push(link_path);
+            symbol_tables.push((source_path.as_str(), symbols));
+        }
+    }
+
+    // Every file stopped early
+    if link_paths.is_empty() {
+        return Ok(());
+    }
+
+    check_multiple_definitions(&symbol_tables)?;
+
+    // Assemble and link
+    progress(options, "Assembling and linking...");
+    let args = link_args(link_paths, executable_path(options), options);
+    run_cc(options, args).map_err(|e| DriverError {
+        msg: format!("Failed to assemble and link: {}.", e.msg),
+        ..e
+    })
+}
+
+/// Returns the path that [`compile`] links the executable, or shared library, to.
+pub fn executable_path(options: &CompileOptions) -> OsString {
+    // From the book:
+    // > [The driver] must produce an executable in the same directory
+    // > as the input file, with the same name (minus the file extension). In other
+    // > words, if you run ./YOUR_COMPILER /path/to/program.c, it should produce an
+    // > executable at /path/to/program and terminate with an exit code of 0
+    // With multiple files, the executable is named after the first one.
+    let output = options.emit_shared.as_ref().or(options.output.as_ref());
+    match output {
+        Some(output) => OsString::from(output),
+        None if options.source_paths[0] == STDIN_PATH => OsString::from(STDIN_OUTPUT),
+        None => intermediate_path(Path::new(&options.source_paths[0])).into_os_string(),
+    }
+}
+
+/// Returns the constant values that `main` returns which don't fit in an exit
+/// code. Exit codes are truncated to 8 bits, so `return 256;` exits with 0.
+fn truncated_exit_codes(program: &Program) -> Vec<i64> {
+    fn returns(statement: &Statement, values: &mut Vec<i64>) {
+        match statement {
+            Statement::Return(exp) => {
+                if let Some(value) = parser::const_eval::const_eval(exp)
+                    && !(0..=255).contains(&value)
+                {
+                    values.push(value);
+                }
+            }
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                returns(then, values);
+                if let Some(otherwise) = otherwise {
+                    returns(otherwise, values);
+                }
+            }
+            Statement::Compound(items) => {
+                for item in items {
+                    if let BlockItem::Statement(statement) = item {
+                        returns(statement, values);
+                    }
+                }
+            }
+            Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::For { body, .. }
+            | Statement::Switch { body, .. }
+            | Statement::Case { stmt: body, .. }
+            | Statement::Default { stmt: body, .. }
+            | Statement::Labeled { stmt: body, .. } => returns(body, values),
+            Statement::Expression(_)
+            | Statement::Break { .. }
+            | Statement::Continue { .. }
+            | Statement::Goto(_)
+            | Statement::Null => {}
+        }
+    }
+
+    let mut values = Vec::new();
+    for declaration in &program.declarations {
+        if let Declaration::Function(function) = declaration
+            && function.name == "main"
+            && let Some(body) = &function.body
+        {
+            returns(body, &mut values);
+        }
+    }
+    values
+}
+
+/// Builds the C compiler arguments for linking `link_paths`, which are assembly or
+/// object files, into `output_path`.
+fn link_args(
+    link_paths: Vec<OsString>,
+    output_path: OsString,
+    options: &CompileOptions,
+) -> Vec<OsString> {
+    let mut args = link_paths;
+    // The generated code is position-independent, as it only refers to functions,
+    // through the PLT, so it can always be linked into a shared library
+    if options.emit_shared.is_some() {
+        args.push(OsString::from("-shared"));
+    }
+    args.push(OsString::from("-o"));
+    args.push(output_path);
+    for dir in &options.library_paths {
+        args.push(OsString::from(format!("-L{dir}")));
+    }
+    // Libraries must come after the files that use them
+    for library in &options.libraries {
+        args.push(OsString::from(format!("-l{library}")));
+    }
+    args
+}
+
+/// Builds the C compiler arguments for preprocessing `source_path` into
+/// `preprocessed_path`.
+fn preprocess_args(
+    source_path: &OsStr,
+    preprocessed_path: &OsStr,
+    options: &CompileOptions,
+) -> Vec<OsString> {
+    let mut args = vec![OsString::from("-E")];
+    // With -g, the linemarkers are kept to map lines back to the files they came from
+    if !options.debug_info {
+        args.push(OsString::from("-P"));
+    }
+    if options.trigraphs {
+        args.push(OsString::from("-trigraphs"));
+    }
+    for dir in &options.include_dirs {
+        args.push(OsString::from(format!("-I{dir}")));
+    }
+    for define in &options.defines {
+        args.push(OsString::from(format!("-D{define}")));
+    }
+    args.push(source_path.to_owned());
+    args.push(OsString::from("-o"));
+    args.push(preprocessed_path.to_owned());
+    args
+}
+
+/// Checks that `source_path` is a regular file, or a symlink to one. Anything else,
+/// like a FIFO, could make the C compiler hang waiting for input.
+fn check_regular_file(source_path: &Path) -> Result<(), DriverError> {
+    let kind = match fs::metadata(source_path).map(|metadata| metadata.file_type()) {
+        Ok(file_type) if file_type.is_file() => return Ok(()),
+        Ok(file_type) if file_type.is_dir() => "a directory",
+        Ok(file_type) if file_type.is_fifo() => "a FIFO",
+        Ok(file_type) if file_type.is_socket() => "a socket",
+        Ok(file_type) if file_type.is_char_device() || file_type.is_block_device() => "a device",
+        Ok(_) => "a special file",
+        Err(_) => {
+            return Err(DriverError {
+                code: ExitCode::NoInput,
+                msg: format!("fatal: \"{}\" is not a file.", source_path.display()),
+                ..Default::default()
+            });
+        }
+    };
+    Err(DriverError {
+        code: ExitCode::NoInput,
+        msg: format!(
+            "fatal: \"{}\" is {kind}, not a regular file.",
+            source_path.display()
+        ),
+        ..Default::default()
+    })
+}
+
+/// Whether the source at `source_path` has already been preprocessed, so the
+/// preprocessor is skipped.
+fn is_preprocessed(source_path: &Path) -> bool {
+    source_path.extension() == Some(OsStr::new(PREPROCESSED_EXTENSION))
+}
+
+/// Returns the path of `source_path` without its extension, which the paths of
+/// intermediate files are based on.
+fn intermediate_path(source_path: &Path) -> PathBuf {
+    let input_dir = source_path.parent().unwrap_or(Path::new("/"));
+    // Unwrap is safe, as callers check that the path is a file
+    let input_basename_stem = source_path.file_stem().map(Path::new).unwrap();
+    input_dir.join(input_basename_stem)
+}
+
+/// Compiles a single source file, at `source_path`.
+///
+/// Returns the path of the file to link, which is the generated assembly, or with
+/// `--save-temps` the object file, along with the symbol table from type checking.
+/// Returns `None` if `options` asked to stop before linking.
+fn compile_source(
+    source_path: &str,
+    options: &CompileOptions,
+) -> Result<Option<(OsString, SymbolTable)>, DriverError> {
+    let source_path = Path::new(source_path);
+    check_regular_file(source_path)?;
+
+    // Intermediate files always go next to the source, even if the executable doesn't
+    let intermediate_path = intermediate_path(source_path);
+    let intermediate_path = intermediate_path.as_os_str();
+
+    let already_preprocessed = is_preprocessed(source_path);
+    let source_path = source_path.as_os_str();
+
+    let preprocessed_path = if already_preprocessed {
+        OsString::from(source_path)
+    } else {
+        progress(options, "Preprocessing...");
+        let mut preprocessed_path = OsString::from(intermediate_path);
+        preprocessed_path.push(".i");
+        let args = preprocess_args(source_path, &preprocessed_path, options);
+        run_cc(options, args).map_err(|e| DriverError {
+            msg: format!("Failed to preprocess: {}.", e.msg),
+            ..e
+        })?;
+        preprocessed_path
+    };
+
+    if options.preprocess_only {
+        let preprocessed = fs::read_to_string(&preprocessed_path)?;
+        match options.output.as_deref() {
+            Some(output) if output != "-" => fs::write(output, preprocessed)?,
+            _ => print!("{preprocessed}"),
+        }
+        return Ok(None);
+    }
+
+    progress(options, "Tokenizing...");
+    // The preprocessor replaces trigraphs itself, so they are only left in source
+    // that skipped it
+    let mut source = lexer::read_source(
+        preprocessed_path.as_os_str(),
+        already_preprocessed && options.trigraphs,
+    )?;
+    if already_preprocessed {
+        let (checked, warnings) =
+            lexer::check_diagnostic_directives(&source).map_err(DriverError::from)?;
+        for warning in warnings {
+            eprintln!(
+                "warning: line {}: #warning {}",
+                warning.line, warning.message
+            );
+        }
+        if let Cow::Owned(checked) = checked {
+            source = checked;
+        }
+    }
+    let (stripped, source_lines) = lexer::strip_linemarkers(&source);
+    if let Cow::Owned(stripped) = stripped {
+        source = stripped;
+    }
+    // Spans of lex and parse errors are in the preprocessed source
+    let in_source = |e: DriverError| DriverError {
+        path: Some(preprocessed_path.to_string_lossy().into_owned()),
+        ..e
+    };
+    let max_identifier_len = options
+        .max_identifier_length
+        .unwrap_or(lexer::DEFAULT_MAX_IDENTIFIER_LEN);
+    let mut tokens = lexer::Lexer::new()
+        .with_max_identifier_len(max_identifier_len)
+        .tokenize_str_with_spans(&source)
+        .map_err(|e| in_source(e.into()))?;
+    if already_preprocessed {
+        lexer::expand_predefined_macros(&mut tokens, &source, &preprocessed_path.to_string_lossy());
+    }
+    if let Some(max_len) = options.warn_identifier_length {
+        for name in lexer::long_identifiers(tokens.iter().map(|(token, _)| token), max_len) {
+            eprintln!("warning: identifier `{name}` is longer than {max_len} characters");
+        }
+    }
+
+    if options.dump_tokens {
+        print!("{}", token_lines(&tokens, &source));
+        return Ok(None);
+    }
+
+    if options.lex {
+        return Ok(None);
+    }
+
+    // Sections of the listing are added as each stage finishes
+    let mut listing = String::new();
+    if options.listing.is_some() {
+        push_listing_section(&mut listing, "Tokens", &token_lines(&tokens, &source));
+    }
+
+    progress(options, "Parsing...");
+    let mut program =
+        parser::parse_with_spans(tokens).map_err(|e| in_source(e.into_driver_error(&source)))?;
+
+    if options.dump_ast_json {
+        let json = serde_json::to_string_pretty(&program).map_err(|e| DriverError {
+            code: ExitCode::Internal,
+            msg: format!("Failed to serialize AST: {e}."),
+            ..Default::default()
+        })?;
+        println!("{json}");
+        return Ok(None);
+    }
+
+    if options.parse {
+        return Ok(None);
+    }
+
+    semantics::resolve(&mut program)?;
+    semantics::label_loops(&mut program)?;
+    semantics::resolve_labels(&mut program)?;
+    let symbols = semantics::typecheck(&program)?;
+    if options.listing.is_some() {
+        push_listing_section(&mut listing, "AST", &parser::pretty::pretty_print(&program));
+    }
+    if options.warn_truncated_exit_codes {
+        for value in truncated_exit_codes(&program) {
+            eprintln!(
+                "warning: `main` returns {value}, but exit codes are truncated to 8 bits, so it exits with {}",
+                value.rem_euclid(256)
+            );
+        }
+    }
+
+    let mut tacky = tacky::gen_tacky(&program);
+    tacky::fold_constants(&mut tacky);
+    if options.listing.is_some() {
+        push_listing_section(&mut listing, "TACKY", &tacky.to_string());
+    }
+    if options.tacky {
+        print!("{tacky}");
+        return Ok(None);
+    }
+    let mut asm = codegen::codegen(&tacky);
+    let max_stack_bytes = options.max_stack_bytes.unwrap_or(DEFAULT_MAX_STACK_BYTES);
+    for function in &mut asm.functions {
+        let stack_size = codegen::replace_pseudos(function)?;
+        // Stack sizes are never negative
+        if stack_size as u32 > max_stack_bytes {
+            return Err(DriverError {
+                code: ExitCode::DataErr,
+                msg: format!(
+                    "fatal: `{}` needs {stack_size} bytes of stack, more than the limit of {max_stack_bytes}.",
+                    function.name
+                ),
+                ..Default::default()
+            });
+        }
+        codegen::fixup_instructions(function, stack_size);
+    }
+
+    if options.codegen {
+        return Ok(None);
+    }
+
+    progress(options, "Emitting assembly...");
+    let assembly = match &options.only_function {
+        Some(name) => codegen::emit::emit_only(&asm, name).ok_or_else(|| DriverError {
+            code: ExitCode::Usage,
+            msg: format!("fatal: no function named `{name}` to emit."),
+            ..Default::default()
+        })?,
+        None if options.debug_info => {
+            let line_info = line_info(
+                &program,
+                &source,
+                &source_lines,
+                &preprocessed_path.to_string_lossy(),
+            );
+            codegen::emit::emit_with_lines(&asm, &line_info)
+        }
+        None if options.emit_cfi => codegen::emit::emit_with_cfi(&asm),
+        None => codegen::emit::emit(&asm),
+    };
+    if let Some(listing_path) = &options.listing {
+        push_listing_section(&mut listing, "Assembly", &assembly);
+        fs::write(listing_path, listing)?;
+    }
+    if assembly_to_stdout(options) {
+        print!("{assembly}");
+        return Ok(None);
+    }
+
+    let assembly_path = assembly_path(intermediate_path, options);
+    fs::write(&assembly_path, assembly)?;
+
+    // With --only-function, the other functions are left undefined, so there is
+    // nothing to link
+    if options.assembly || options.only_function.is_some() {
+        return Ok(None);
+    }
+
+    if options.compile_only || options.save_temps {
+        progress(options, "Assembling...");
+        let object_path = object_path(intermediate_path, options);
+        let args = [
+            OsStr::new("-c"),
+            &assembly_path,
+            OsStr::new("-o"),
+            &object_path,
+        ];
+        run_cc(options, args).map_err(|e| DriverError {
+            msg: format!("Failed to assemble: {}.", e.msg),
+            ..e
+        })?;
+        if options.compile_only {
+            return Ok(None);
+        }
+        return Ok(Some((object_path, symbols)));
+    }
+
+    Ok(Some((assembly_path, symbols)))
+}
+
+/// Checks that no function is defined by more than one of the sources being linked,
+/// given the symbol table of each, so the error can name the sources rather than
+/// leaving it to the linker.
+///
+/// Functions are the only names declared at file scope, so the only ones shared
+/// between sources; every variable in a table is local to its own source.
+fn check_multiple_definitions(symbol_tables: &[(&str, SymbolTable)]) -> Result<(), DriverError> {
+    let mut defined_in = HashMap::new();
+    for (source_path, symbols) in symbol_tables {
+        let mut defined = symbols
+            .iter()
+            .filter(|(_, info)| matches!(info, SymbolInfo::Function { defined: true, .. }))
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        // Report the same name each time, whatever order the table is in
+        defined.sort_unstable();
+        for name in defined {
+            if let Some(first) = defined_in.insert(name, *source_path) {
+                return Err(DriverError {
+                    code: ExitCode::DataErr,
+                    msg: format!(
+                        "fatal: multiple definition of `{name}`, in {first} and {source_path}."
+                    ),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path to write the assembly of the source at `intermediate_path` to.
+fn assembly_path(intermediate_path: &OsStr, options: &CompileOptions) -> OsString {
+    // With -S, -o names the assembly file, as it does the object file with -c
+    let output = options.output.as_ref().filter(|_| options.assembly);
+    match options.asm_out.as_ref().or(output) {
+        Some(asm_out) => OsString::from(asm_out),
+        None => {
+            let mut assembly_path = OsString::from(intermediate_path);
+            assembly_path.push(".s");
+            assembly_path
+        }
+    }
+}
+
+/// Returns the path to assemble the source at `intermediate_path` to.
+fn object_path(intermediate_path: &OsStr, options: &CompileOptions) -> OsString {
+    // Without -c, -o names the executable
+    let output = options.output.as_ref().filter(|_| options.compile_only);
+    match output {
+        Some(output) => OsString::from(output),
+        None => {
+            let mut object_path = OsString::from(intermediate_path);
+            object_path.push(".o");
+            object_path
+        }
+    }
+}
+
+/// Describes the steps [`compile`] would take with `options`, one per line.
+///
+/// Commands for the C compiler are shown as they would be run. The compiler's
+/// own step, from preprocessed source to assembly, is shown as `compile`.
+fn plan(options: &CompileOptions) -> String {
+    let cc = options.cc.as_deref().unwrap_or(DEFAULT_CC);
+    let stops_before_emission = options.lex
+        || options.dump_tokens
+        || options.parse
+        || options.dump_ast_json
+        || options.tacky
+        || options.codegen;
+
+    let mut lines = Vec::new();
+    let mut link_paths = Vec::new();
+    for source_path in &options.source_paths {
+        let source_path = if source_path == STDIN_PATH {
+            stdin_path()
+        } else {
+            PathBuf::from(source_path)
+        };
+        let intermediate_path = intermediate_path(&source_path);
+        let intermediate_path = intermediate_path.as_os_str();
+
+        let preprocessed_path = if is_preprocessed(&source_path) {
+            source_path.into_os_string()
+        } else {
+            let mut preprocessed_path = OsString::from(intermediate_path);
+            preprocessed_path.push(".i");
+            let args = preprocess_args(source_path.as_os_str(), &preprocessed_path, options);
+            lines.push(command_line(cc, &args));
+            preprocessed_path
+        };
+        if options.preprocess_only {
+            continue;
+        }
+
+        let mut args = vec![preprocessed_path];
+        if stops_before_emission {
+            lines.push(command_line("compile", &args));
+            continue;
+        }
+        let assembly_path = if assembly_to_stdout(options) {
+            OsString::from("-")
+        } else {
+            assembly_path(intermediate_path, options)
+        };
+        args.extend([OsString::from("-o"), assembly_path.clone()]);
+        lines.push(command_line("compile", &args));
+        if options.assembly || options.only_function.is_some() {
+            continue;
+        }
+
+        if options.compile_only || options.save_temps {
+            let object_path = object_path(intermediate_path, options);
+            let args = [
+                OsString::from("-c"),
+                assembly_path,
+                OsString::from("-o"),
+                object_path.clone(),
+            ];
+            lines.push(command_line(cc, &args));
+            if !options.compile_only {
+                link_paths.push(object_path);
+            }
+        } else {
+            link_paths.push(assembly_path);
+        }
+    }
+
+    if !link_paths.is_empty() {
+        let args = link_args(link_paths, executable_path(options), options);
+        lines.push(command_line(cc, &args));
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Renders `tokens`, lexed from `source`, one per line with its line and column.
+fn token_lines(tokens: &[(lexer::Token, lexer::Span)], source: &str) -> String {
+    let mut lines = String::new();
+    for (token, span) in tokens {
+        let (line, column) = span.line_column(source);
+        lines.push_str(&format!("{line}:{column} {token}\n"));
+    }
+    lines
+}
+
+/// Returns where each function defined in `program`, parsed from `source`, is
+/// defined, in the files that `source_lines` says its lines came from. Lines from no
+/// file, as when the preprocessor left no linemarkers, are located in
+/// `preprocessed_path`.
+fn line_info(
+    program: &Program,
+    source: &str,
+    source_lines: &lexer::SourceLines,
+    preprocessed_path: &str,
+) -> codegen::emit::LineInfo {
+    let mut line_info = codegen::emit::LineInfo::default();
+    for declaration in &program.declarations {
+        if let Declaration::Function(function) = declaration
+            && function.body.is_some()
+            && let Some(span) = function.span
+        {
+            let line = span.line_column(source).0;
+            let (file, line) = match source_lines.locate(line) {
+                Some((index, line)) => (source_lines.files[index].as_str(), line),
+                None => (preprocessed_path, line),
+            };
+            // Files are numbered from 1
+            let number = match line_info.files.iter().position(|f| f == file) {
+                Some(index) => index + 1,
+                None => {
+                    line_info.files.push(file.to_string());
+                    line_info.files.len()
+                }
+            };
+            line_info
+                .function_lines
+                .insert(function.name.clone(), (number, line));
+        }
+    }
+    line_info
+}
+
+/// Adds a section to the `--listing` output, headed by `title`.
+fn push_listing_section(listing: &mut String, title: &str, body: &str) {
+    if !listing.is_empty() {
+        listing.push('\n');
+    }
+    listing.push_str(&format!("==== {title} ====\n"));
+    listing.push_str(body);
+}
+
+/// Whether `-S -o -` asked for the assembly to be written to stdout.
+fn assembly_to_stdout(options: &CompileOptions) -> bool {
+    options.assembly && options.output.as_deref() == Some("-")
+}
+
+/// Whether `-E` is writing the preprocessed source to stdout, as it does unless
+/// `-o` names a file.
+fn preprocessed_to_stdout(options: &CompileOptions) -> bool {
+    options.preprocess_only && options.output.as_deref().is_none_or(|output| output == "-")
+}
+
+/// Prints a progress message. This goes to stderr if stdout is taken by the
+/// assembly or preprocessed source.
+fn progress(options: &CompileOptions, msg: &str) {
+    if assembly_to_stdout(options) || preprocessed_to_stdout(options) {
+        eprintln!("{msg}");
+    } else {
+        println!("{msg}");
+    }
+}
+
+/// Runs the C compiler chosen by `--cc` or `CC` with `args`, printing the
+/// command first if `--verbose` is set.
+fn run_cc<I, S>(options: &CompileOptions, args: I) -> Result<(), DriverError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let cc = options.cc.as_deref().unwrap_or(DEFAULT_CC);
+    let args = args
+        .into_iter()
+        .map(|arg| arg.as_ref().to_owned())
+        .collect::<Vec<_>>();
+    if options.verbose {
+        progress(options, &command_line(cc, &args));
+    }
+
+    let status = Command::new(cc).args(args).status().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            // Like a shell, exit with 127 if the command can't be found
+            DriverError {
+                code: ExitCode::CommandNotFound,
+                msg: format!("could not find `{cc}` on PATH; install it or pass --cc"),
+                ..Default::default()
+            }
+        } else {
+            DriverError {
+                code: ExitCode::OsErr,
+                msg: format!("{e}"),
+                ..Default::default()
+            }
+        }
+    })?;
+
+    if !status.success() {
+        // On Unix, status.code() returns None if the process was killed
+        // by a signal. If the process was killed by a signal, we still
+        // want to terminate.
+        return Err(DriverError {
+            code: status.code().map_or(ExitCode::OsErr, ExitCode::Child),
+            msg: if let Some(code) = status.code() {
+                format!("{cc} terminated with exit code {code}")
+            } else {
+                format!("{cc} killed by some signal")
+            },
+            ..Default::default()
+        });
+    }
+
+    Ok(())
+}
+
+/// Renders the command `cc` with `args` for printing, quoting arguments that
+/// contain whitespace.
+fn command_line(cc: &str, args: &[OsString]) -> String {
+    let mut line = cc.to_string();
+    for arg in args {
+        let arg = arg.to_string_lossy();
+        if arg.contains(char::is_whitespace) {
+            line.push_str(&format!(" '{arg}'"));
+        } else {
+            line.push(' ');
+            line.push_str(&arg);
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASIC_MAIN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../test_c_source/basic_main.c");
+
+    #[test]
+    fn test_run_cc() {
+        let args = CompileOptions::default();
+        run_cc(&args, ["-E", "-P", BASIC_MAIN, "-o", "/dev/null"]).unwrap();
+
+        let err = run_cc(&args, ["-E", "-P", "invalid_path.c", "-o", "/dev/null"]).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::Child(1),
+                msg: "gcc terminated with exit code 1".to_string(),
+                ..Default::default()
+            }
+        );
+
+        let args = CompileOptions {
+            cc: Some("no-such-compiler".to_string()),
+            ..Default::default()
+        };
+        let err = run_cc(&args, ["--version"]).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::CommandNotFound,
+                msg: "could not find `no-such-compiler` on PATH; install it or pass --cc"
+                    .to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_command_line() {
+        let args = [
+            OsString::from("-E"),
+            OsString::from("my dir/main.c"),
+            OsString::from("-o"),
+            OsString::from("main.i"),
+        ];
+        assert_eq!(
+            command_line("gcc", &args),
+            "gcc -E 'my dir/main.c' -o main.i"
+        );
+
+        let args = CompileOptions {
+            verbose: true,
+            ..Default::default()
+        };
+        run_cc(&args, ["-E", "-P", BASIC_MAIN, "-o", "/dev/null"]).unwrap();
+    }
+
+    #[test]
+    fn test_cc_not_found() {
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            cc: Some("/nonexistent/cc".to_string()),
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::CommandNotFound,
+                msg: "Failed to preprocess: could not find `/nonexistent/cc` on PATH; install it or pass --cc."
+                    .to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_driver_happy_paths() {
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            lex: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            parse: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            codegen: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string()],
+            dump_ast_json: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+    }
+
+    #[test]
+    fn test_asm_out() {
+        // Compile a copy of the source in its own directory, so we can check that nothing
+        // is written next to it without racing against other tests
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_asm_out_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let asm_out = dir.join("scratch.s");
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            asm_out: Some(asm_out.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        assert!(asm_out.is_file());
+        assert!(!dir.join("main.s").exists());
+        assert!(dir.join("main").is_file());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_output() {
+        let dir = std::env::temp_dir().join(format!("compiler_test_output_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let output = dir.join("foo");
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            output: Some(output.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(output.is_file());
+        assert!(!dir.join("main").exists());
+
+        // Without -o, the executable is named after the source file
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(dir.join("main").is_file());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_emit_shared() {
+        let dir = std::env::temp_dir().join(format!("compiler_test_shared_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("answer.c");
+        std::fs::write(
+            &source_path,
+            "int six(void) { return 6; }\nint answer(void) { return six() * 7; }\n",
+        )
+        .unwrap();
+        let library_path = dir.join("libanswer.so");
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            emit_shared: Some(library_path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        // Safety: the library has no initializers, and `answer` has this signature
+        unsafe {
+            let library = libloading::Library::new(&library_path).unwrap();
+            let answer: libloading::Symbol<unsafe extern "C" fn() -> i32> =
+                library.get(b"answer").unwrap();
+            assert_eq!(answer(), 42);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_exit_codes() {
+        let program = parser::parse(
+            lexer::tokenize_str(
+                "int f(void) { return 300; }
+                int main(void) { if (f()) { return 256; } while (1) return -1; return 255; }",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(truncated_exit_codes(&program), [256, -1]);
+    }
+
+    #[test]
+    fn test_token_lines() {
+        let source = "int main(void){return 2;}";
+        let tokens = lexer::tokenize_str_with_spans(source).unwrap();
+        assert_eq!(
+            token_lines(&tokens, source),
+            "1:1 `int`
+1:5 identifier `main`
+1:9 `(`
+1:10 `void`
+1:14 `)`
+1:15 `{`
+1:16 `return`
+1:23 constant `2`
+1:24 `;`
+1:25 `}`
+"
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_token_lines_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            dump_tokens: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_line_info() {
+        let source = "# 1 \"main.c\"\nint f(void);\nint g(int a)\n{ return f(); }\n\nint main(void) { return g(1); }";
+        let (stripped, source_lines) = lexer::strip_linemarkers(source);
+        let program =
+            parser::parse_with_spans(lexer::tokenize_str_with_spans(&stripped).unwrap()).unwrap();
+        assert_eq!(
+            line_info(&program, &stripped, &source_lines, "main.i"),
+            codegen::emit::LineInfo {
+                files: vec!["main.c".to_string()],
+                function_lines: HashMap::from([
+                    ("g".to_string(), (1, 2)),
+                    ("main".to_string(), (1, 5))
+                ]),
+            }
+        );
+        // Without linemarkers, lines are of the preprocessed source
+        let (_, source_lines) = lexer::strip_linemarkers(&stripped);
+        assert_eq!(
+            line_info(&program, &stripped, &source_lines, "main.i"),
+            codegen::emit::LineInfo {
+                files: vec!["main.i".to_string()],
+                function_lines: HashMap::from([
+                    ("g".to_string(), (1, 3)),
+                    ("main".to_string(), (1, 6))
+                ]),
+            }
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_debug_info_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let header_path = dir.join("answer.h");
+        std::fs::write(&header_path, "int answer(void) { return 42; }\n").unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(
+            &source_path,
+            "#define ONE 1\n#include \"answer.h\"\n\nint main(void)\n{\n    return answer() + ONE;\n}\n",
+        )
+        .unwrap();
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            debug_info: true,
+            save_temps: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        // Lines are of the files as written, not of the preprocessed source
+        let assembly = std::fs::read_to_string(dir.join("main.s")).unwrap();
+        assert!(assembly.starts_with(&format!(
+            "\t.file 1 {header_path:?}\n\t.file 2 {source_path:?}\n"
+        )));
+        assert!(assembly.contains("answer:\n\t.cfi_startproc\n\t.loc 1 1\n"));
+        assert!(assembly.contains("main:\n\t.cfi_startproc\n\t.loc 2 4\n"));
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(43));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_listing() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_listing_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let listing_path = dir.join("listing.txt");
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            listing: Some(listing_path.to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let listing = std::fs::read_to_string(&listing_path).unwrap();
+        let headers = listing
+            .lines()
+            .filter(|line| line.starts_with("===="))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "==== Tokens ====",
+                "==== AST ====",
+                "==== TACKY ====",
+                "==== Assembly ===="
+            ]
+        );
+        assert!(listing.contains("1:1 `int`\n"), "{listing}");
+        assert!(listing.contains("Program(\n"), "{listing}");
+        assert!(listing.contains("main():\n"), "{listing}");
+        assert!(listing.contains(".globl main"), "{listing}");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan() {
+        let options = CompileOptions {
+            source_paths: vec!["dir/main.c".to_string()],
+            defines: vec!["FOO".to_string()],
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&options),
+            "gcc -E -P -DFOO dir/main.c -o dir/main.i
+compile dir/main.i -o dir/main.s
+gcc dir/main.s -o dir/main
+"
+        );
+        // Nothing is run, so the missing source is never noticed
+        compile(&options).unwrap();
+        assert!(!Path::new("dir").exists());
+
+        let options = CompileOptions {
+            compile_only: true,
+            output: Some("out.o".to_string()),
+            ..options
+        };
+        assert_eq!(
+            plan(&options),
+            "gcc -E -P -DFOO dir/main.c -o dir/main.i
+compile dir/main.i -o dir/main.s
+gcc -c dir/main.s -o out.o
+"
+        );
+
+        let options = CompileOptions {
+            source_paths: vec!["main.c".to_string(), "other.c".to_string()],
+            defines: vec![],
+            parse: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            plan(&options),
+            "gcc -E -P main.c -o main.i\ncompile main.i\ngcc -E -P other.c -o other.i\ncompile other.i\n"
+        );
+
+        // Preprocessed source is compiled as is
+        let options = CompileOptions {
+            source_paths: vec!["main.i".to_string()],
+            ..options
+        };
+        assert_eq!(plan(&options), "compile main.i\n");
+    }
+
+    #[test]
+    fn test_save_temps() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_save_temps_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main_path = dir.join("main.c");
+        std::fs::write(&main_path, "int f(void);\nint main(void) { return f(); }\n").unwrap();
+        let other_path = dir.join("other.c");
+        std::fs::write(&other_path, "int f(void) { return 2; }\n").unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![
+                main_path.to_str().unwrap().to_string(),
+                other_path.to_str().unwrap().to_string(),
+            ],
+            save_temps: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        for stem in ["main", "other"] {
+            for extension in ["i", "s", "o"] {
+                let path = dir.join(format!("{stem}.{extension}"));
+                assert!(path.exists(), "{} is missing", path.display());
+            }
+        }
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_assembly() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_assembly_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            assembly: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(dir.join("main.s").is_file());
+        assert!(!dir.join("main").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_assembly_output() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_asm_output_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let source_path = source_path.to_str().unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_string()],
+            assembly: true,
+            output: Some(dir.join("out.s").to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(dir.join("out.s").is_file());
+        assert!(!dir.join("main.s").exists());
+
+        // The assembly goes to stdout, so no file is written
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_string()],
+            assembly: true,
+            output: Some("-".to_string()),
+            ..Default::default()
+        };
+        assert!(assembly_to_stdout(&args));
+        compile(&args).unwrap();
+        assert!(!dir.join("main.s").exists());
+        assert!(!dir.join("-").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compile_only() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_compile_only_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            compile_only: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(dir.join("main.o").is_file());
+        assert!(!dir.join("main").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_multiple_sources() {
+        let dir = std::env::temp_dir().join(format!(
+            "compiler_test_multiple_sources_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main_path = dir.join("main.c");
+        std::fs::write(
+            &main_path,
+            "int three(void);\nint main(void) { return three(); }\n",
+        )
+        .unwrap();
+        let three_path = dir.join("three.c");
+        std::fs::write(&three_path, "int three(void) { return 3; }\n").unwrap();
+        let source_paths = vec![
+            main_path.to_str().unwrap().to_string(),
+            three_path.to_str().unwrap().to_string(),
+        ];
+
+        let args = CompileOptions {
+            source_paths: source_paths.clone(),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(3));
+
+        // A function defined in two files is caught before linking
+        let other_path = dir.join("other.c");
+        std::fs::write(&other_path, "int three(void) { return 4; }\n").unwrap();
+        let args = CompileOptions {
+            source_paths: [
+                source_paths.clone(),
+                vec![other_path.to_str().unwrap().to_string()],
+            ]
+            .concat(),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(&args).unwrap_err(),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: format!(
+                    "fatal: multiple definition of `three`, in {} and {}.",
+                    source_paths[1],
+                    other_path.display()
+                ),
+                ..Default::default()
+            }
+        );
+
+        // Errors say which file they came from
+        let missing_path = dir.join("missing.c").to_str().unwrap().to_string();
+        let args = CompileOptions {
+            source_paths: [source_paths, vec![missing_path.clone()]].concat(),
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(&args).unwrap_err(),
+            DriverError {
+                code: ExitCode::NoInput,
+                msg: format!("{missing_path}: fatal: \"{missing_path}\" is not a file."),
+                ..Default::default()
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_logical_operators_in_arithmetic() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_logical_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(
+            &source_path,
+            "int main(void) { return (1 && 1) + (0 || 1); }\n",
+        )
+        .unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preprocess_args() {
+        let args = CompileOptions {
+            source_paths: vec!["main.c".to_string()],
+            include_dirs: vec!["include".to_string()],
+            defines: vec!["FOO=1".to_string(), "BAR".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            preprocess_args(OsStr::new("main.c"), OsStr::new("main.i"), &args),
+            [
+                "-E",
+                "-P",
+                "-Iinclude",
+                "-DFOO=1",
+                "-DBAR",
+                "main.c",
+                "-o",
+                "main.i"
+            ]
+        );
+
+        let args = CompileOptions {
+            trigraphs: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            preprocess_args(OsStr::new("main.c"), OsStr::new("main.i"), &args),
+            ["-E", "-P", "-trigraphs", "main.c", "-o", "main.i"]
+        );
+
+        // With -g, linemarkers are kept
+        let args = CompileOptions {
+            debug_info: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            preprocess_args(OsStr::new("main.c"), OsStr::new("main.i"), &args),
+            ["-E", "main.c", "-o", "main.i"]
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_preprocess_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        std::fs::write(
+            dir.join("include/answer.h"),
+            "int answer(void) { return FOO + 1; }\n",
+        )
+        .unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(
+            &source_path,
+            "#include <answer.h>\nint main(void) { return answer(); }\n",
+        )
+        .unwrap();
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            include_dirs: vec![dir.join("include").to_str().unwrap().to_string()],
+            defines: vec!["FOO=41".to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(42));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trigraphs() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_trigraphs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        // A directive, and a line continuation, spelled with trigraphs
+        std::fs::write(
+            &source_path,
+            "??=define ANSWER ??/\n42\nint main(void) ??< return ANSWER ??! 1; ??>\n",
+        )
+        .unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            trigraphs: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(43));
+
+        // Without --trigraphs, they are left alone
+        let args = CompileOptions {
+            trigraphs: false,
+            ..args
+        };
+        assert!(compile(&args).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preprocessed_source() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_preprocessed_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.i");
+        std::fs::write(
+            &source_path,
+            "int main(void) {\n    int __FILE = 1;\n    return __LINE__ + 39 + __FILE;\n}\n",
+        )
+        .unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        let status = Command::new(dir.join("main")).status().unwrap();
+        assert_eq!(status.code(), Some(43));
+        // The source is never overwritten by the preprocessor
+        assert!(
+            std::fs::read_to_string(&source_path)
+                .unwrap()
+                .contains("__LINE__")
+        );
+
+        // There is no preprocessor to act on #warning and #error, so the compiler does
+        std::fs::write(
+            &source_path,
+            "#warning careful\nint main(void) { return 0; }\n",
+        )
+        .unwrap();
+        compile(&args).unwrap();
+        std::fs::write(
+            &source_path,
+            "#warning careful\n#error stop\nint main(void) { return 0; }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            compile(&args).unwrap_err(),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "line 2: #error stop".to_string(),
+                ..Default::default()
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_operator_semantics() {
+        // Exit codes are the low byte of the return value
+        let cases = [
+            ("return 2 + 3 * 4;", 14),
+            ("return (2 + 3) * 4;", 20),
+            ("return 10 - 3 - 2;", 5),
+            ("return 7 / 2;", 3),
+            ("return -5 / 2;", 254),
+            ("return 7 % 3;", 1),
+            ("return -7 % 3;", 255),
+            ("return -(-3);", 3),
+            ("return ~0 & 255;", 255),
+            ("return 6 | 9;", 15),
+            ("return 6 ^ 3;", 5),
+            ("return 1 << 4;", 16),
+            ("return -16 >> 2;", 252),
+            ("return 0 || 2;", 1),
+            ("return 2 && 0;", 0),
+            ("return 0 ? 1 : 2;", 2),
+            ("int a = 5; a = a * 2; return a;", 10),
+            ("int a = 1; goto end; a = 2; end: return a;", 1),
+            (
+                "int a = 0; again: a = a + 1; if (a < 5) goto again; return a;",
+                5,
+            ),
+            (
+                "int a = 0; switch (2) { case 1: a = 1; case 2: a = a + 2; case 3: a = a + 3; break; default: a = 9; } return a;",
+                5,
+            ),
+            (
+                "switch (7) { case 1: return 1; default: return 4; } return 0;",
+                4,
+            ),
+            ("switch (1) { case 4294967297: return 3; } return 0;", 3),
+            // __builtin_expect is only a hint, so behaves exactly like its first argument
+            (
+                "int x = 1; if (__builtin_expect(x, 1)) return 3; return 4;",
+                3,
+            ),
+            (
+                "int x = 0; if (__builtin_expect(x, 1)) return 3; return 4;",
+                4,
+            ),
+            (
+                "int x = 0; if (__builtin_expect(x, 0)) return 3; return 4;",
+                4,
+            ),
+            ("int x = 0; __builtin_expect(1, x = 7); return x;", 7),
+        ];
+
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_operators_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (i, (body, expected)) in cases.into_iter().enumerate() {
+            let source_path = dir.join(format!("case_{i}.c"));
+            std::fs::write(&source_path, format!("int main(void) {{ {body} }}\n")).unwrap();
+
+            let args = CompileOptions {
+                source_paths: vec![source_path.to_str().unwrap().to_string()],
+                ..Default::default()
+            };
+            compile(&args).unwrap();
+            let executable = dir.join(format!("case_{i}"));
+            let status = Command::new(executable).status().unwrap();
+            assert_eq!(status.code(), Some(expected), "{body}");
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_link_args() {
+        let args = CompileOptions {
+            source_paths: vec!["main.c".to_string()],
+            libraries: vec!["m".to_string(), "foo".to_string()],
+            library_paths: vec!["/opt/lib".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            link_args(
+                vec![OsString::from("main.s")],
+                OsString::from("main"),
+                &args
+            ),
+            ["main.s", "-o", "main", "-L/opt/lib", "-lm", "-lfoo"]
+        );
+
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_link_args_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            libraries: vec!["m".to_string()],
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(dir.join("main").is_file());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_codegen() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_codegen_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(&source_path, "int main(void) { return ~-3; }\n").unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            codegen: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+        assert!(!dir.join("main.s").exists());
+        assert!(!dir.join("main").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/missing_semicolon.c"
+                )
+                .to_string(),
+            ],
+            codegen: true,
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: expected `;`, found `}` at line 3, column 1.".to_string(),
+                span: Some((30, 31)),
+                path: Some(
+                    concat!(
+                        env!("CARGO_MANIFEST_DIR"),
+                        "/../test_c_source/missing_semicolon.i"
+                    )
+                    .to_string()
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_stack_bytes() {
+        let dir = std::env::temp_dir().join(format!(
+            "compiler_test_max_stack_bytes_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // basic_main needs no stack at all
+        let basic_main_path = dir.join("basic_main.c");
+        std::fs::copy(BASIC_MAIN, &basic_main_path).unwrap();
+        let args = CompileOptions {
+            source_paths: vec![basic_main_path.to_str().unwrap().to_string()],
+            codegen: true,
+            max_stack_bytes: Some(0),
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        let source_path = dir.join("main.c");
+        std::fs::write(
+            &source_path,
+            "int main(void) { int a = 1; int b = 2; return a; }\n",
+        )
+        .unwrap();
+        let args = |max_stack_bytes| CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            codegen: true,
+            max_stack_bytes: Some(max_stack_bytes),
+            ..Default::default()
+        };
+
+        compile(&args(16)).unwrap();
+        assert_eq!(
+            compile(&args(8)).unwrap_err(),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "fatal: `main` needs 16 bytes of stack, more than the limit of 8.".to_string(),
+                ..Default::default()
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_function() {
+        let dir = std::env::temp_dir().join(format!(
+            "compiler_test_only_function_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(
+            &source_path,
+            "int f(void) { return 1; }\nint main(void) { return 2; }\n",
+        )
+        .unwrap();
+        let args = |name: &str| CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            only_function: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        compile(&args("f")).unwrap();
+        let assembly = std::fs::read_to_string(dir.join("main.s")).unwrap();
+        assert!(assembly.contains("\nf:\n"));
+        assert!(!assembly.contains("main:"));
+        assert!(!dir.join("main").exists());
+
+        assert_eq!(
+            compile(&args("g")).unwrap_err(),
+            DriverError {
+                code: ExitCode::Usage,
+                msg: "fatal: no function named `g` to emit.".to_string(),
+                ..Default::default()
+            }
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_preprocessor_token() {
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/invalid_preprocessor_token.c"
+                )
+                .to_string(),
+            ],
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::Child(1),
+                msg: "Failed to preprocess: gcc terminated with exit code 1.".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_source_token() {
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/invalid_source_token.c"
+                )
+                .to_string(),
+            ],
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: expected `int`, found identifier `in` at line 1, column 1."
+                    .to_string(),
+                span: Some((0, 2)),
+                path: Some(
+                    concat!(
+                        env!("CARGO_MANIFEST_DIR"),
+                        "/../test_c_source/invalid_source_token.i"
+                    )
+                    .to_string()
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_lex() {
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/invalid_lex_token.c"
+                )
+                .to_string(),
+            ],
+            lex: true,
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to lex: invalid token.".to_string(),
+                span: Some((30, 31)),
+                path: Some(
+                    concat!(
+                        env!("CARGO_MANIFEST_DIR"),
+                        "/../test_c_source/invalid_lex_token.i"
+                    )
+                    .to_string()
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn test_max_identifier_length() {
+        let dir = std::env::temp_dir().join(format!(
+            "compiler_test_max_identifier_length_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+
+        let args = |max_identifier_length| CompileOptions {
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            lex: true,
+            max_identifier_length: Some(max_identifier_length),
+            ..Default::default()
+        };
+        compile(&args(4)).unwrap();
+        let err = compile(&args(3)).unwrap_err();
+        assert_eq!(
+            err.msg,
+            "Failed to lex: identifier is longer than the limit of 3 characters."
+        );
+        assert_eq!(err.span, Some((4, 8)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_parse() {
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/missing_semicolon.c"
+                )
+                .to_string(),
+            ],
+            parse: true,
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: expected `;`, found `}` at line 3, column 1.".to_string(),
+                span: Some((30, 31)),
+                path: Some(
+                    concat!(
+                        env!("CARGO_MANIFEST_DIR"),
+                        "/../test_c_source/missing_semicolon.i"
+                    )
+                    .to_string()
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn test_undeclared_variable() {
+        let args = CompileOptions {
+            source_paths: vec![
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../test_c_source/undeclared_variable.c"
+                )
+                .to_string(),
+            ],
+            codegen: true,
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed semantic analysis: use of undeclared `y`.".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_undefined_symbol_token() {
+        let source_path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../test_c_source/undefined_symbol.c"
+        )
+        .to_string();
+        let args = CompileOptions {
+            source_paths: vec![source_path.clone()],
+            ..Default::default()
+        };
+        let err = compile(&args).unwrap_err();
+        assert_eq!(
+            err,
+            DriverError {
+                code: ExitCode::NoInput,
+                msg: format!("fatal: \"{source_path}\" is not a file."),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_irregular_source_file() {
+        let dir =
+            std::env::temp_dir().join(format!("compiler_test_irregular_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dir.c")).unwrap();
+        let fifo_path = dir.join("fifo.c");
+        let status = Command::new("mkfifo").arg(&fifo_path).status().unwrap();
+        assert!(status.success());
+        let link_path = dir.join("link.c");
+        std::os::unix::fs::symlink(BASIC_MAIN, &link_path).unwrap();
+
+        for (path, kind) in [("dir.c", "a directory"), ("fifo.c", "a FIFO")] {
+            let source_path = dir.join(path).to_str().unwrap().to_string();
+            // The C compiler is never run, which would hang reading the FIFO
+            let args = CompileOptions {
+                source_paths: vec![source_path.clone()],
+                cc: Some("/nonexistent/cc".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                compile(&args).unwrap_err(),
+                DriverError {
+                    code: ExitCode::NoInput,
+                    msg: format!("fatal: \"{source_path}\" is {kind}, not a regular file."),
+                    ..Default::default()
+                }
+            );
+        }
+
+        // A symlink to a regular file is fine
+        let args = CompileOptions {
+            source_paths: vec![link_path.to_str().unwrap().to_string()],
+            parse: true,
+            ..Default::default()
+        };
+        compile(&args).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_source_extension() {
+        let args = CompileOptions {
+            source_paths: vec![BASIC_MAIN.to_string(), "foo.cpp".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            compile(&args).unwrap_err(),
+            DriverError {
+                code: ExitCode::Usage,
+                msg: "fatal: \"foo.cpp\" is not a C source file (expected .c or .i).".to_string(),
+                ..Default::default()
+            }
+        );
+
+        for source_path in ["main.C", "main", "c"] {
+            let args = CompileOptions {
+                source_paths: vec![source_path.to_string()],
+                ..Default::default()
+            };
+            let err = compile(&args).unwrap_err();
+            assert!(err.msg.contains("is not a C source file"), "{source_path}");
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Tests that drive the compiler only through its public API, as an embedder would.
+
+use std::process::Command;
+
+use compiler::CompileOptions;
+
+#[test]
+fn test_compile() {
+    let dir = std::env::temp_dir().join(format!("compiler_api_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.c");
+    std::fs::write(&source_path, "int main(void) { return 1 + 2; }\n").unwrap();
+    let output_path = dir.join("three");
+
+    let options = CompileOptions {
+        source_paths: vec![source_path.to_str().unwrap().to_string()],
+        output: Some(output_path.to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    compiler::compile(&options).unwrap();
+    assert_eq!(compiler::executable_path(&options), output_path);
+    let status = Command::new(&output_path).status().unwrap();
+    assert_eq!(status.code(), Some(3));
+
+    // Errors come back to the caller, rather than exiting
+    std::fs::write(&source_path, "int main(void) { return 1 + ; }\n").unwrap();
+    let err = compiler::compile(&options).unwrap_err();
+    assert_eq!(err.code, errors::ExitCode::DataErr);
+    assert!(err.msg.starts_with("Failed to parse"), "{}", err.msg);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
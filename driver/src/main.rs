@@ -1,19 +1,37 @@
-use clap::Parser;
-use std::ffi::{OsStr, OsString};
+use clap::{Parser, ValueEnum};
+use std::ffi::OsStr;
+use std::io::{self, IsTerminal};
+use std::os::unix::process::ExitStatusExt;
 use std::path::Path;
 use std::process::{Command, exit};
 
+use compiler::CompileOptions;
 use errors::DriverError;
 
 /// Clap program arguments
 #[derive(Parser, Debug, Default)]
 #[command(about = "A C compiler", long_about = None)]
 struct Args {
-    /// Path to C source file to compile
-    source_path: String,
+    /// Paths to C source files to compile and link together,
+    /// or `-` to read one from stdin. Files ending in `.i`
+    /// are compiled without preprocessing
+    #[arg(required = true)]
+    source_paths: Vec<String>,
+
+    /// Preprocess, print the preprocessed source (or
+    /// write it to -o), then stop
+    #[arg(
+        short = 'E',
+        long,
+        conflicts_with_all = [
+            "lex", "dump_tokens", "parse", "tacky", "codegen", "dump_ast_json", "assembly",
+            "compile_only", "emit_shared", "asm_out", "only_function", "run", "listing"
+        ]
+    )]
+    preprocess_only: bool,
 
     /// Run the lexer, but stop before parsing
-    #[arg(short, long)]
+    #[arg(long)]
     lex: bool,
 
     /// Run the lexer and parser, but stop before
@@ -21,251 +39,309 @@ struct Args {
     #[arg(short, long)]
     parse: bool,
 
+    /// Run the lexer, parser, and TACKY generation, print
+    /// the TACKY, then stop
+    #[arg(long)]
+    tacky: bool,
+
     /// Perform lexing, parsing, and assembly
     /// generation, but stop before code emission
-    #[arg(short, long)]
+    #[arg(long)]
     codegen: bool,
-}
 
-fn main() {
-    let args = Args::parse();
-    let res = driver(args);
-
-    if let Err(e) = res {
-        eprintln!("{}", e.msg);
-        exit(e.exit_code);
-    }
+    /// Emit assembly, but stop before assembling
+    /// and linking
+    #[arg(short = 'S')]
+    assembly: bool,
+
+    /// Assemble into an object file, but stop before
+    /// linking
+    #[arg(short = 'c')]
+    compile_only: bool,
+
+    /// Warn about identifiers longer than this many
+    /// characters
+    #[arg(long, value_name = "N")]
+    warn_identifier_length: Option<usize>,
+
+    /// Fail on identifiers longer than this many characters
+    /// [default: 1024]
+    #[arg(long, value_name = "N")]
+    max_identifier_length: Option<usize>,
+
+    /// Replace trigraph sequences (e.g. `??(` for `[`)
+    /// before preprocessing
+    #[arg(long)]
+    trigraphs: bool,
+
+    /// Run the lexer, print each token with its line
+    /// and column, then stop
+    #[arg(long, hide = true)]
+    dump_tokens: bool,
+
+    /// Run the lexer and parser, print the AST as JSON,
+    /// then stop
+    #[arg(long)]
+    dump_ast_json: bool,
+
+    /// Write the executable to this path, instead of
+    /// next to the source file
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<String>,
+
+    /// Link into a shared library at this path, instead of
+    /// an executable
+    #[arg(long, value_name = "PATH", conflicts_with = "output")]
+    emit_shared: Option<String>,
+
+    /// Link against this library
+    #[arg(short = 'l', value_name = "NAME")]
+    libraries: Vec<String>,
+
+    /// Search this directory for libraries
+    #[arg(short = 'L', value_name = "DIR")]
+    library_paths: Vec<String>,
+
+    /// Search this directory for headers
+    #[arg(short = 'I', value_name = "DIR")]
+    include_dirs: Vec<String>,
+
+    /// Define a macro for the preprocessor
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    defines: Vec<String>,
+
+    /// Write the generated assembly to this path, instead
+    /// of next to the source file
+    #[arg(long, visible_alias = "output-assembly-to", value_name = "PATH")]
+    asm_out: Option<String>,
+
+    /// Compile the whole program, but only emit assembly
+    /// for the named function, then stop
+    #[arg(long, value_name = "NAME")]
+    only_function: Option<String>,
+
+    /// Fail if any function needs more than this many bytes
+    /// of stack [default: 1048576]
+    #[arg(long, value_name = "BYTES")]
+    max_stack_bytes: Option<u32>,
+
+    /// Preprocess, assemble, and link with this C compiler
+    /// [default: gcc]
+    #[arg(long, env = "CC", value_name = "PATH")]
+    cc: Option<String>,
+
+    /// Keep the preprocessed source, assembly, and object
+    /// file of every source next to it, like gcc's
+    /// -save-temps
+    #[arg(long, conflicts_with = "only_function")]
+    save_temps: bool,
+
+    /// Emit line numbers, so a debugger can show the
+    /// source of each function
+    #[arg(short = 'g', conflicts_with = "only_function")]
+    debug_info: bool,
+
+    /// Emit CFI directives, so debuggers and unwinders
+    /// can walk the stack. Always on with -g
+    #[arg(long, conflicts_with = "only_function")]
+    emit_cfi: bool,
+
+    /// Print the commands run to preprocess, assemble,
+    /// and link
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Print the commands that would be run, without
+    /// running them or writing any files
+    #[arg(long, conflicts_with = "run")]
+    dry_run: bool,
+
+    /// Run the executable after linking, and exit with
+    /// its exit code
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "lex", "dump_tokens", "parse", "tacky", "codegen", "dump_ast_json", "assembly",
+            "compile_only", "emit_shared", "only_function"
+        ]
+    )]
+    run: bool,
+
+    /// Write a listing of the tokens, AST, TACKY, and
+    /// assembly to this path
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["lex", "dump_tokens", "parse", "tacky", "codegen", "dump_ast_json"]
+    )]
+    listing: Option<String>,
+
+    /// When to color error messages
+    #[arg(long, value_enum, value_name = "WHEN", default_value_t)]
+    color: ColorChoice,
 }
 
-fn driver(driver_args: Args) -> Result<(), DriverError> {
-    let source_path = Path::new(&driver_args.source_path);
-    if !source_path.is_file() {
-        return Err(DriverError {
-            exit_code: 1,
-            msg: format!("fatal: \"{}\" is not a file.", driver_args.source_path),
-        });
-    }
-
-    // From the book:
-    // > [The driver] must produce an executable in the same directory
-    // > as the input file, with the same name (minus the file extension). In other
-    // > words, if you run ./YOUR_COMPILER /path/to/program.c, it should produce an
-    // > executable at /path/to/program and terminate with an exit code of 0
-    let input_dir = source_path.parent().unwrap_or(Path::new("/"));
-    // Unwrap is safe, due to is_file check above
-    let input_basename_stem = source_path.file_stem().map(Path::new).unwrap();
-    let output_path = input_dir.join(input_basename_stem);
-    let output_path = output_path.as_os_str();
-
-    let source_path = source_path.as_os_str();
-
-    println!("Preprocessing...");
-    let mut preprocessed_path = OsString::from(output_path);
-    preprocessed_path.push(".i");
-    let args = [
-        OsStr::new("-E"),
-        OsStr::new("-P"),
-        source_path,
-        OsStr::new("-o"),
-        &preprocessed_path,
-    ];
-    run_gcc(args).map_err(|e|
-        DriverError {
-            msg: format!("Failed to run gcc preprocessing: {}.", e.msg),
-            ..e
-        }
-    )?;
+/// Values of `--color`
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum ColorChoice {
+    /// Color errors if stderr is a terminal
+    #[default]
+    Auto,
+    /// Always color errors
+    Always,
+    /// Never color errors
+    Never,
+}
 
-    println!("Tokenizing...");
-    let tokens = lexer::tokenize(preprocessed_path.as_os_str()).map_err(|_|
-        DriverError {
-            msg: "Lex step failed due to invalid token".to_string(),
-            exit_code: 1,
+impl ColorChoice {
+    /// Whether to color errors printed to stderr
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
         }
-    );
-
-    if driver_args.lex {
-        return Ok(());
     }
+}
 
-    // Parsing will go here
+fn main() {
+    let args = Args::parse();
+    let trigraphs = args.trigraphs;
+    let color = args.color.enabled();
+    let res = driver(args);
 
-    if driver_args.parse {
-        return Ok(());
+    match res {
+        Ok(exit_code) => exit(exit_code),
+        Err(e) => {
+            // Show the source the error points into, if it can still be read
+            let source = e
+                .path
+                .as_ref()
+                .filter(|_| e.span.is_some())
+                .and_then(|path| lexer::read_source(OsStr::new(path), trigraphs).ok());
+            let rendered = match source {
+                Some(source) => e.render_with_color(&source, color),
+                None => e.msg.clone(),
+            };
+            eprintln!("{} {rendered}", errors::error_prefix(color));
+            exit(e.exit_code());
+        }
     }
+}
 
-    // Codegen will go here
+/// Compiles the program, returning the code to exit with: the program's own exit
+/// code with `--run`, and otherwise 0.
+fn driver(driver_args: Args) -> Result<i32, DriverError> {
+    let options = CompileOptions::from(&driver_args);
+    compiler::compile(&options)?;
 
-    if driver_args.codegen {
-        return Ok(());
+    if !driver_args.run {
+        return Ok(0);
     }
 
-    // TODO Remove this stubbing
-    println!("Running stub compiler...");
-    let mut assembly_path = OsString::from(output_path);
-    assembly_path.push(".s");
-    let args = [OsStr::new("-S"), OsStr::new("-O"), &preprocessed_path, OsStr::new("-o"), &assembly_path];
-    run_gcc(args).map_err(|e|
-        DriverError {
-            msg: format!("Failed to compile: {}.", e.msg),
-            ..e
-        }
-    )?;
-
-    // Assemble and link
-    println!("Assembling and linking...");
-    run_gcc([&assembly_path, OsStr::new("-o"), output_path]).map_err(|e|
-        DriverError {
-            msg: format!("Failed to assemble and link: {}.", e.msg),
-            ..e
-        }
-    )?;
-
-    Ok(())
+    println!("Running...");
+    // Join onto `.`, so a bare file name isn't looked up on PATH
+    let status = Command::new(Path::new(".").join(compiler::executable_path(&options))).status()?;
+    // Like a shell, report a program killed by a signal as 128 plus the signal
+    Ok(status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1))
 }
 
-fn run_gcc<I, S>(args: I) -> Result<(), DriverError>
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-{
-    // Uncomment to debug run_gcc command
-    // let args = dbg!(args.into_iter().map(|s| s.as_ref().to_owned()).collect::<Vec<_>>());
-
-    let status = Command::new("gcc").args(args).status().map_err(|e|
-        DriverError {
-            exit_code: 1,
-            msg: format!("{e}"),
+impl From<&Args> for CompileOptions {
+    fn from(args: &Args) -> Self {
+        Self {
+            source_paths: args.source_paths.clone(),
+            preprocess_only: args.preprocess_only,
+            lex: args.lex,
+            parse: args.parse,
+            tacky: args.tacky,
+            codegen: args.codegen,
+            assembly: args.assembly,
+            compile_only: args.compile_only,
+            warn_identifier_length: args.warn_identifier_length,
+            max_identifier_length: args.max_identifier_length,
+            trigraphs: args.trigraphs,
+            dump_tokens: args.dump_tokens,
+            dump_ast_json: args.dump_ast_json,
+            output: args.output.clone(),
+            emit_shared: args.emit_shared.clone(),
+            libraries: args.libraries.clone(),
+            library_paths: args.library_paths.clone(),
+            include_dirs: args.include_dirs.clone(),
+            defines: args.defines.clone(),
+            asm_out: args.asm_out.clone(),
+            only_function: args.only_function.clone(),
+            max_stack_bytes: args.max_stack_bytes,
+            cc: args.cc.clone(),
+            save_temps: args.save_temps,
+            debug_info: args.debug_info,
+            emit_cfi: args.emit_cfi,
+            verbose: args.verbose,
+            dry_run: args.dry_run,
+            listing: args.listing.clone(),
+            // `main` only returns to the user with --run
+            warn_truncated_exit_codes: args.run,
         }
-    )?;
-
-    if !status.success() {
-        // On Unix, status.code() returns None if the process was killed
-        // by a signal. If the process was killed by a signal, we still
-        // want to terminate.
-        return Err(DriverError {
-            exit_code: status.code().unwrap_or(1),
-            msg: if let Some(code) = status.code() {
-                format!("gcc terminated with exit code {code}")
-            } else {
-                "gcc killed by some signal".to_string()
-            },
-        });
     }
-
-    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    const BASIC_MAIN: &str =
-        concat!(env!("CARGO_MANIFEST_DIR"), "/../test_c_source/basic_main.c");
-
     #[test]
-    fn test_run_gcc() {
-        run_gcc(["-E", "-P", BASIC_MAIN, "-o", "/dev/null"]).unwrap();
-
-        let err = run_gcc(["-E", "-P", "invalid_path.c", "-o", "/dev/null"]).unwrap_err();
-        assert_eq!(
-            err,
-            DriverError {
-                exit_code: 1,
-                msg: "gcc terminated with exit code 1".to_string()
-            }
-        );
+    fn test_compile_options() {
+        let args = Args::parse_from([
+            "driver", "main.c", "-D", "FOO=1", "-I", "include", "-DBAR", "-lm", "-L", "/opt/lib",
+            "--run",
+        ]);
+        let options = CompileOptions::from(&args);
+        assert_eq!(options.source_paths, ["main.c"]);
+        assert_eq!(options.defines, ["FOO=1", "BAR"]);
+        assert_eq!(options.include_dirs, ["include"]);
+        assert_eq!(options.libraries, ["m"]);
+        assert_eq!(options.library_paths, ["/opt/lib"]);
+        assert!(options.warn_truncated_exit_codes);
     }
 
     #[test]
-    fn test_driver_happy_paths() {
-        let args = Args {
-            source_path: BASIC_MAIN.to_string(),
-            ..Default::default()
-        };
-        driver(args).unwrap();
-
-        let args = Args {
-            source_path: BASIC_MAIN.to_string(),
-            lex: true,
-            ..Default::default()
-        };
-        driver(args).unwrap();
-
-        let args = Args {
-            source_path: BASIC_MAIN.to_string(),
-            parse: true,
-            ..Default::default()
-        };
-        driver(args).unwrap();
-
-        let args = Args {
-            source_path: BASIC_MAIN.to_string(),
-            codegen: true,
-            ..Default::default()
-        };
-        driver(args).unwrap();
+    fn test_run_conflicts() {
+        // Each of these stops before linking, so there would be nothing to run
+        let flags: [&[&str]; 10] = [
+            &["--lex"],
+            &["--dump-tokens"],
+            &["--parse"],
+            &["--tacky"],
+            &["--codegen"],
+            &["--dump-ast-json"],
+            &["-S"],
+            &["-c"],
+            &["--emit-shared", "lib.so"],
+            &["--only-function", "main"],
+        ];
+        for flag in flags {
+            let args = [&["driver", "main.c", "--run"], flag].concat();
+            assert!(Args::try_parse_from(args).is_err(), "{flag:?}");
+        }
     }
 
     #[test]
-    fn test_invalid_preprocessor_token() {
-        let args = Args {
-            source_path: concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../test_c_source/invalid_preprocessor_token.c"
-            )
-            .to_string(),
-            ..Default::default()
-        };
-        let err = driver(args).unwrap_err();
-        assert_eq!(
-            err,
-            DriverError {
-                exit_code: 1,
-                msg: "Failed to run gcc preprocessing: gcc terminated with exit code 1."
-                    .to_string()
-            }
-        );
-    }
+    fn test_run() {
+        let dir = std::env::temp_dir().join(format!("driver_test_run_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_path = dir.join("main.c");
+        std::fs::write(&source_path, "int main(void) { return 257; }\n").unwrap();
 
-    #[test]
-    fn test_invalid_source_token() {
+        // The exit code is truncated to 8 bits
         let args = Args {
-            source_path: concat!(
-                env!("CARGO_MANIFEST_DIR"),
-                "/../test_c_source/invalid_source_token.c"
-            )
-            .to_string(),
+            source_paths: vec![source_path.to_str().unwrap().to_string()],
+            run: true,
             ..Default::default()
         };
-        let err = driver(args).unwrap_err();
-        assert_eq!(
-            err,
-            DriverError {
-                exit_code: 1,
-                msg: "Failed to parse. See errors above.".to_string()
-            }
-        );
-    }
+        assert_eq!(driver(args), Ok(1));
 
-    #[test]
-    fn test_undefined_symbol_token() {
-        let source_path = concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/../test_c_source/undefined_symbol.c"
-        )
-        .to_string();
-        let args = Args {
-            source_path: source_path.clone(),
-            ..Default::default()
-        };
-        let err = driver(args).unwrap_err();
-        assert_eq!(
-            err,
-            DriverError {
-                exit_code: 1,
-                msg: format!("fatal: \"{source_path}\" is not a file.")
-            }
-        );
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
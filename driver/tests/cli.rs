@@ -0,0 +1,220 @@
+//! Tests that need the output of the driver binary itself.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const BASIC_MAIN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../test_c_source/basic_main.c");
+const MISSING_SEMICOLON: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../test_c_source/missing_semicolon.c"
+);
+
+#[test]
+fn test_assembly_to_stdout() {
+    let dir = std::env::temp_dir().join(format!("driver_cli_test_stdout_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.c");
+    std::fs::copy(BASIC_MAIN, &source_path).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .args(["-S", "-o", "-"])
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(".globl main"), "{stdout}");
+    // Progress messages go to stderr instead
+    assert!(!stdout.contains("Emitting assembly..."));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Emitting assembly..."), "{stderr}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_run_truncated_exit_code() {
+    let dir = std::env::temp_dir().join(format!("driver_cli_test_run_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.c");
+    std::fs::write(&source_path, "int main(void) { return 257; }\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("--run")
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("warning: `main` returns 257, but exit codes are truncated to 8 bits, so it exits with 1"),
+        "{stderr}"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_compile_stdin() {
+    let dir = std::env::temp_dir().join(format!("driver_cli_test_stdin_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("-")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"int main(void) { return 3; }\n")
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    // The executable defaults to `a.out`, and the copy of stdin is removed
+    let status = Command::new(dir.join("a.out")).status().unwrap();
+    assert_eq!(status.code(), Some(3));
+    let stdin_path = std::env::temp_dir().join(format!("driver_stdin_{}.c", child.id()));
+    assert!(!stdin_path.exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_preprocess_only() {
+    let dir = std::env::temp_dir().join(format!("driver_cli_test_e_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.c");
+    std::fs::write(
+        &source_path,
+        "#define ANSWER 42\nint main(void) { return ANSWER; }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("-E")
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "int main(void) { return 42; }");
+    // Nothing past preprocessing runs
+    assert!(!dir.join("main.s").exists());
+    assert!(!dir.join("main").exists());
+
+    // With -o, the preprocessed source is written there instead
+    let output_path = dir.join("out.i");
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("-E")
+        .arg("-o")
+        .arg(&output_path)
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap().trim(),
+        "int main(void) { return 42; }"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tacky() {
+    let dir = std::env::temp_dir().join(format!("driver_cli_test_tacky_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.c");
+    std::fs::write(&source_path, "int main(void) { return -5; }\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .arg("--tacky")
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // The TACKY follows the progress messages, after constant folding
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with("Parsing...\nmain():\n    return -5\n"),
+        "{stdout}"
+    );
+    assert!(!dir.join("main.s").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_render_lex_error() {
+    let source_path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test_c_source/invalid_lex_token.c"
+    );
+    let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+        .args(["--lex", source_path])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = format!(
+        "error: Failed to lex: invalid token.
+ --> {}.i:2:14
+  |
+2 |     return 0 @ 1;
+  |              ^
+",
+        source_path.strip_suffix(".c").unwrap()
+    );
+    assert_eq!(stderr, expected);
+}
+
+#[test]
+fn test_exit_codes() {
+    let exit_code = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_driver"))
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    // Usage errors, missing input, and invalid programs each have their own code
+    assert_eq!(exit_code(&["foo.cpp"]), Some(64));
+    assert_eq!(exit_code(&["no_such_file.c"]), Some(66));
+    assert_eq!(exit_code(&["-", "-"]), Some(64));
+    assert_eq!(exit_code(&["-c", "-"]), Some(64));
+    assert_eq!(exit_code(&["--parse", MISSING_SEMICOLON]), Some(65));
+    assert_eq!(
+        exit_code(&["--cc", "no-such-compiler", BASIC_MAIN]),
+        Some(127)
+    );
+}
+
+#[test]
+fn test_color() {
+    let stderr = |color: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_driver"))
+            .args(["--color", color, "--parse", MISSING_SEMICOLON])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let plain = stderr("never");
+    assert!(plain.starts_with("error: Failed to parse"), "{plain}");
+    assert!(!plain.contains('\x1b'));
+
+    let colored = stderr("always");
+    assert!(
+        colored.starts_with("\x1b[1;31merror:\x1b[0m Failed to parse"),
+        "{colored}"
+    );
+    assert!(colored.contains("\x1b[1;31m^\x1b[0m"), "{colored}");
+
+    // Output captured by a test is not a terminal
+    assert_eq!(stderr("auto"), plain);
+}
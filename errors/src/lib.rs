@@ -1,23 +1,277 @@
 use std::io;
 
+/// Kind of error, which decides the code the program exits with.
+///
+/// Codes follow `sysexits.h` where it has one, so scripts can tell, for example,
+/// a usage error from a program that failed to compile.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExitCode {
+    /// The command line was invalid
+    Usage,
+    /// The source being compiled was invalid
+    DataErr,
+    /// A source file could not be found
+    NoInput,
+    /// A bug in the compiler
+    #[default]
+    Internal,
+    /// The operating system failed to do something, like start a process
+    OsErr,
+    /// Reading or writing a file failed
+    IoErr,
+    /// The C compiler used to preprocess, assemble, and link could not be found
+    CommandNotFound,
+    /// A process the driver ran failed with this exit code
+    Child(i32),
+}
+
+impl ExitCode {
+    /// The code to exit with
+    pub fn code(self) -> i32 {
+        match self {
+            ExitCode::Usage => 64,
+            ExitCode::DataErr => 65,
+            ExitCode::NoInput => 66,
+            ExitCode::Internal => 70,
+            ExitCode::OsErr => 71,
+            ExitCode::IoErr => 74,
+            // Like a shell
+            ExitCode::CommandNotFound => 127,
+            ExitCode::Child(code) => code,
+        }
+    }
+}
+
 /// Encapsulates data for exiting the program due to an error error.
 ///
 /// We cannot derive any `From` implementations using `thiserror::Error`,
 /// because main always expects an exit code to be able to exit with.
 /// Therefore, all `From` implementations are handwritten.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct DriverError {
-    /// Exit code to exit the program with, due to the error
-    pub exit_code: i32,
+    /// Kind of error, which decides the code to exit the program with
+    pub code: ExitCode,
     /// Error message
     pub msg: String,
+    /// Byte range of the source that caused the error, if known
+    pub span: Option<(usize, usize)>,
+    /// Path of the source file the span is in, if known
+    pub path: Option<String>,
+}
+
+impl DriverError {
+    /// Exit code to exit the program with, due to the error
+    pub fn exit_code(&self) -> i32 {
+        self.code.code()
+    }
+
+    /// Renders the error for printing, given the `source` its span is in.
+    ///
+    /// Without a span, this is just the message. With one, the message is followed
+    /// by the location and the line of `source` the span starts on, with the span
+    /// underlined, like rustc:
+    ///
+    /// ```text
+    /// Failed to lex: invalid token.
+    ///  --> main.c:1:21
+    ///   |
+    /// 1 | int main(void) { ret@rn 0; }
+    ///   |                     ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_with_color(source, false)
+    }
+
+    /// Renders the error like [`render`](Self::render), but if `color` is set, with
+    /// ANSI escapes coloring the gutter blue and the underline red.
+    pub fn render_with_color(&self, source: &str, color: bool) -> String {
+        let Some((start, end)) = self.span else {
+            return self.msg.clone();
+        };
+        let start = start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[..start].matches('\n').count() + 1;
+        // The `\r` of a `\r\n` line ending isn't part of the line
+        let line_end = match source[..line_end].strip_suffix('\r') {
+            Some(text) if text.len() >= start => text.len(),
+            _ => line_end,
+        };
+        let text = &source[line_start..line_end];
+        let before = &source[line_start..start];
+        let column = before.chars().count() + 1;
+
+        // Keep tabs, so the underline lines up however they are displayed
+        let indent = before
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        // An empty span, like the end of the input, still gets one `^`
+        let underline_len = source[start..end.clamp(start, line_end)].chars().count();
+        let underline = paint("^".repeat(underline_len.max(1)), RED, color);
+
+        let gutter = " ".repeat(line.to_string().len());
+        let location = match &self.path {
+            Some(path) => format!("{path}:{line}:{column}"),
+            None => format!("{line}:{column}"),
+        };
+        let arrow = paint(format!("{gutter}-->"), BLUE, color);
+        let bar = paint(format!("{gutter} |"), BLUE, color);
+        let numbered_bar = paint(format!("{line} |"), BLUE, color);
+        format!(
+            "{}\n{arrow} {location}\n{bar}\n{numbered_bar} {text}\n{bar} {indent}{underline}",
+            self.msg
+        )
+    }
+}
+
+/// Prefix of an error printed for the user, which is red if `color` is set.
+pub fn error_prefix(color: bool) -> String {
+    paint("error:".to_string(), RED, color)
+}
+
+/// ANSI escape for bold red text
+const RED: &str = "\x1b[1;31m";
+/// ANSI escape for bold blue text
+const BLUE: &str = "\x1b[1;34m";
+/// ANSI escape resetting the text style
+const RESET: &str = "\x1b[0m";
+
+/// Wraps `text` in the ANSI escapes for `style`, if `color` is set.
+fn paint(text: String, style: &str, color: bool) -> String {
+    if color {
+        format!("{style}{text}{RESET}")
+    } else {
+        text
+    }
 }
 
 impl From<io::Error> for DriverError {
     fn from(e: std::io::Error) -> Self {
         Self {
-            exit_code: 1,
+            code: ExitCode::IoErr,
             msg: format!("I/O error: {e}"),
+            ..Default::default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(ExitCode::Usage.code(), 64);
+        assert_eq!(ExitCode::DataErr.code(), 65);
+        assert_eq!(ExitCode::NoInput.code(), 66);
+        assert_eq!(ExitCode::Internal.code(), 70);
+        assert_eq!(ExitCode::OsErr.code(), 71);
+        assert_eq!(ExitCode::IoErr.code(), 74);
+        assert_eq!(ExitCode::CommandNotFound.code(), 127);
+        assert_eq!(ExitCode::Child(3).code(), 3);
+
+        let err = DriverError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(err.code, ExitCode::IoErr);
+        assert_eq!(err.exit_code(), 74);
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "int main(void) {\n    return 1 @ 2;\n}\n";
+        let err = DriverError {
+            code: ExitCode::DataErr,
+            msg: "Failed to lex: invalid token.".to_string(),
+            span: Some((30, 31)),
+            path: Some("main.c".to_string()),
+        };
+        assert_eq!(
+            err.render(source),
+            "Failed to lex: invalid token.
+ --> main.c:2:14
+  |
+2 |     return 1 @ 2;
+  |              ^"
+        );
+
+        // The underline covers the whole span, but stops at the end of the line
+        let err = DriverError {
+            span: Some((21, source.len())),
+            path: None,
+            ..err
+        };
+        assert_eq!(
+            err.render(source),
+            "Failed to lex: invalid token.
+ --> 2:5
+  |
+2 |     return 1 @ 2;
+  |     ^^^^^^^^^^^^^"
+        );
+
+        let err = DriverError { span: None, ..err };
+        assert_eq!(err.render(source), "Failed to lex: invalid token.");
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let source = "int main(void) { ret@rn 0; }";
+        let err = DriverError {
+            msg: "Failed to lex: invalid token.".to_string(),
+            span: Some((20, 21)),
+            ..Default::default()
+        };
+        assert_eq!(
+            err.render_with_color(source, true),
+            "Failed to lex: invalid token.
+\x1b[1;34m -->\x1b[0m 1:21
+\x1b[1;34m  |\x1b[0m
+\x1b[1;34m1 |\x1b[0m int main(void) { ret@rn 0; }
+\x1b[1;34m  |\x1b[0m                     \x1b[1;31m^\x1b[0m"
+        );
+        assert_eq!(err.render_with_color(source, false), err.render(source));
+        assert!(!err.render(source).contains('\x1b'));
+
+        assert_eq!(error_prefix(true), "\x1b[1;31merror:\x1b[0m");
+        assert_eq!(error_prefix(false), "error:");
+    }
+
+    #[test]
+    fn test_render_crlf() {
+        let source = "int main(void) {\r\n    return 1 @ 2;\r\n}\r\n";
+        let err = DriverError {
+            msg: "Failed to lex: invalid token.".to_string(),
+            span: Some((31, source.len())),
+            ..Default::default()
+        };
+        assert_eq!(
+            err.render(source),
+            "Failed to lex: invalid token.
+ --> 2:14
+  |
+2 |     return 1 @ 2;
+  |              ^^^^"
+        );
+    }
+
+    #[test]
+    fn test_render_end_of_input() {
+        let source = "int main(void) {";
+        let err = DriverError {
+            code: ExitCode::DataErr,
+            msg: "Failed to parse: unexpected end of file.".to_string(),
+            span: Some((source.len(), source.len())),
+            path: None,
+        };
+        assert_eq!(
+            err.render(source),
+            "Failed to parse: unexpected end of file.
+ --> 1:17
+  |
+1 | int main(void) {
+  |                 ^"
+        );
+    }
+}
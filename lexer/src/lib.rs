@@ -1,22 +1,147 @@
 use regex::Regex;
+use std::borrow::Cow;
 use std::cmp;
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
 
-use errors::DriverError;
+use errors::{DriverError, ExitCode};
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     Identifier(String),
     Constant(String),
+    /// Contents of a string literal, without the quotes and with escape sequences
+    /// left as written
+    StringLiteral(String),
     IntKeyword,
+    LongKeyword,
+    CharKeyword,
     VoidKeyword,
     ReturnKeyword,
+    IfKeyword,
+    ElseKeyword,
+    StaticIfKeyword,
+    DoKeyword,
+    WhileKeyword,
+    ForKeyword,
+    BreakKeyword,
+    ContinueKeyword,
+    GotoKeyword,
+    SwitchKeyword,
+    CaseKeyword,
+    DefaultKeyword,
     OpenParenthesis,
     CloseParenthesis,
     OpenBrace,
     CloseBrace,
     Semicolon,
+    Comma,
+    QuestionMark,
+    Colon,
+    Plus,
+    Hyphen,
+    Asterisk,
+    ForwardSlash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    Exclamation,
+    DoubleLessThan,
+    DoubleGreaterThan,
+    DoubleAmpersand,
+    DoublePipe,
+    Equal,
+    DoubleEqual,
+    ExclamationEqual,
+    LessThan,
+    LessThanEqual,
+    GreaterThan,
+    GreaterThanEqual,
+}
+
+impl Token {
+    /// Whether this is a keyword, like `int` or `return`
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            Token::IntKeyword
+                | Token::LongKeyword
+                | Token::CharKeyword
+                | Token::VoidKeyword
+                | Token::ReturnKeyword
+                | Token::IfKeyword
+                | Token::ElseKeyword
+                | Token::StaticIfKeyword
+                | Token::DoKeyword
+                | Token::WhileKeyword
+                | Token::ForKeyword
+                | Token::BreakKeyword
+                | Token::ContinueKeyword
+                | Token::GotoKeyword
+                | Token::SwitchKeyword
+                | Token::CaseKeyword
+                | Token::DefaultKeyword
+        )
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "identifier `{name}`"),
+            Token::Constant(value) => write!(f, "constant `{value}`"),
+            Token::StringLiteral(value) => write!(f, "string literal `\"{value}\"`"),
+            Token::IntKeyword => write!(f, "`int`"),
+            Token::LongKeyword => write!(f, "`long`"),
+            Token::CharKeyword => write!(f, "`char`"),
+            Token::VoidKeyword => write!(f, "`void`"),
+            Token::ReturnKeyword => write!(f, "`return`"),
+            Token::IfKeyword => write!(f, "`if`"),
+            Token::ElseKeyword => write!(f, "`else`"),
+            Token::StaticIfKeyword => write!(f, "`_Static_if`"),
+            Token::DoKeyword => write!(f, "`do`"),
+            Token::WhileKeyword => write!(f, "`while`"),
+            Token::ForKeyword => write!(f, "`for`"),
+            Token::BreakKeyword => write!(f, "`break`"),
+            Token::ContinueKeyword => write!(f, "`continue`"),
+            Token::GotoKeyword => write!(f, "`goto`"),
+            Token::SwitchKeyword => write!(f, "`switch`"),
+            Token::CaseKeyword => write!(f, "`case`"),
+            Token::DefaultKeyword => write!(f, "`default`"),
+            Token::OpenParenthesis => write!(f, "`(`"),
+            Token::CloseParenthesis => write!(f, "`)`"),
+            Token::OpenBrace => write!(f, "`{{`"),
+            Token::CloseBrace => write!(f, "`}}`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::QuestionMark => write!(f, "`?`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Hyphen => write!(f, "`-`"),
+            Token::Asterisk => write!(f, "`*`"),
+            Token::ForwardSlash => write!(f, "`/`"),
+            Token::Percent => write!(f, "`%`"),
+            Token::Ampersand => write!(f, "`&`"),
+            Token::Pipe => write!(f, "`|`"),
+            Token::Caret => write!(f, "`^`"),
+            Token::Tilde => write!(f, "`~`"),
+            Token::Exclamation => write!(f, "`!`"),
+            Token::DoubleLessThan => write!(f, "`<<`"),
+            Token::DoubleGreaterThan => write!(f, "`>>`"),
+            Token::DoubleAmpersand => write!(f, "`&&`"),
+            Token::DoublePipe => write!(f, "`||`"),
+            Token::Equal => write!(f, "`=`"),
+            Token::DoubleEqual => write!(f, "`==`"),
+            Token::ExclamationEqual => write!(f, "`!=`"),
+            Token::LessThan => write!(f, "`<`"),
+            Token::LessThanEqual => write!(f, "`<=`"),
+            Token::GreaterThan => write!(f, "`>`"),
+            Token::GreaterThanEqual => write!(f, "`>=`"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -28,43 +153,521 @@ struct TokenInfo {
 }
 
 #[derive(Debug, PartialEq)]
-pub struct LexError;
+pub struct LexError {
+    pub kind: LexErrorKind,
+    /// Span of the input the error is in
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LexErrorKind {
+    /// No token starts with the character at the span
+    InvalidToken,
+    /// The identifier at the span is longer than the lexer allows
+    IdentifierTooLong {
+        /// Most characters an identifier may have
+        max_len: usize,
+    },
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::InvalidToken => write!(f, "invalid token"),
+            LexErrorKind::IdentifierTooLong { max_len } => {
+                write!(
+                    f,
+                    "identifier is longer than the limit of {max_len} characters"
+                )
+            }
+        }
+    }
+}
 
 impl From<LexError> for DriverError {
-    fn from(_: LexError) -> Self {
-        todo!()
+    fn from(e: LexError) -> Self {
+        Self {
+            code: ExitCode::DataErr,
+            msg: format!("Failed to lex: {}.", e.kind),
+            span: Some((e.span.start, e.span.end)),
+            ..Default::default()
+        }
     }
 }
 
-pub fn tokenize(path: &OsStr) -> Result<Vec<Token>, DriverError> {
+/// Byte range of a token in the input it was lexed from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    /// Offset of the first byte of the token
+    pub start: usize,
+    /// Offset just past the last byte of the token
+    pub end: usize,
+}
+
+/// Byte order mark that may start UTF-8 text
+const BYTE_ORDER_MARK: char = '\u{FEFF}';
+
+impl Span {
+    /// Returns the 1-based line and column of the start of the span in `input`.
+    pub fn line_column(&self, input: &str) -> (usize, usize) {
+        let before = &input[..self.start];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        // A byte order mark isn't displayed, so it takes up no column
+        let line_before = &before[line_start..];
+        let line_before = line_before
+            .strip_prefix(BYTE_ORDER_MARK)
+            .filter(|_| line_start == 0)
+            .unwrap_or(line_before);
+        let column = line_before.chars().count() + 1;
+        (line, column)
+    }
+}
+
+/// Tokenizes the file at `path`.
+///
+/// If `trigraphs` is set, trigraph sequences are replaced before tokenizing.
+pub fn tokenize(path: &OsStr, trigraphs: bool) -> Result<Vec<Token>, DriverError> {
+    Ok(tokenize_str(&read_source(path, trigraphs)?)?)
+}
+
+/// Reads the file at `path` to be tokenized, replacing trigraph sequences if
+/// `trigraphs` is set.
+pub fn read_source(path: &OsStr, trigraphs: bool) -> Result<String, DriverError> {
     let source = fs::read_to_string(path)?;
-    Ok(tokenize_str(&source)?)
+    if trigraphs {
+        Ok(replace_trigraphs(&source).into_owned())
+    } else {
+        Ok(source)
+    }
 }
 
-pub fn tokenize_str(input: &str) -> Result<Vec<Token>, LexError> {
-    let mut input = input;
-    let mut tokens = Vec::new();
+/// The nine standard trigraph sequences and the characters they stand for.
+const TRIGRAPHS: [(&str, char); 9] = [
+    ("??=", '#'),
+    ("??(", '['),
+    ("??/", '\\'),
+    ("??)", ']'),
+    ("??'", '^'),
+    ("??<", '{'),
+    ("??!", '|'),
+    ("??>", '}'),
+    ("??-", '~'),
+];
+
+/// Replaces every trigraph sequence in `input` with the character it stands for.
+///
+/// Replacement is a single left-to-right pass, so the output of one replacement
+/// never forms part of another trigraph (`???=` becomes `?#`).
+pub fn replace_trigraphs(input: &str) -> Cow<'_, str> {
+    if !input.contains("??") {
+        return Cow::Borrowed(input);
+    }
 
-    while !input.is_empty() {
-        if input.starts_with(char::is_whitespace) {
-            input = input.trim_start_matches(char::is_whitespace);
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        if let Some((trigraph, replacement)) = TRIGRAPHS.iter().find(|(t, _)| rest.starts_with(t)) {
+            output.push(*replacement);
+            rest = &rest[trigraph.len()..];
         } else {
-            let Some(token_info) = find_token(input) else {
-                return Err(LexError);
-            };
-            tokens.push(token_info.token);
-            input = &input[cmp::min(token_info.len, input.len())..];
+            output.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    Cow::Owned(output)
+}
+
+/// Expands the predefined `__LINE__` and `__FILE__` macros in `tokens`, which were
+/// lexed from `input`, read from `path`.
+///
+/// This is only needed when the source has not been through the preprocessor,
+/// which would otherwise have expanded them. `__LINE__` becomes the 1-based line
+/// number it is on, and `__FILE__` a string literal containing `path`. As this works
+/// on tokens, the names are left alone in string literals and comments.
+pub fn expand_predefined_macros(tokens: &mut [(Token, Span)], input: &str, path: &str) {
+    for (token, span) in tokens {
+        let Token::Identifier(name) = token else {
+            continue;
+        };
+        match name.as_str() {
+            "__LINE__" => *token = Token::Constant(span.line_column(input).0.to_string()),
+            "__FILE__" => {
+                // String literals keep their escape sequences as written
+                let escaped = path.replace('\\', "\\\\").replace('"', "\\\"");
+                *token = Token::StringLiteral(escaped);
+            }
+            _ => {}
+        }
+    }
+}
+
+static DIAGNOSTIC_DIRECTIVE: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r"^[ \t]*#[ \t]*(error|warning)\b[ \t]*(.*?)[ \t]*\r?$").unwrap()
+});
+
+/// A `#error` or `#warning` directive found by [`check_diagnostic_directives`]
+#[derive(Debug, PartialEq)]
+pub struct DiagnosticDirective {
+    /// 1-based line number of the directive
+    pub line: usize,
+    /// Rest of the line after the directive name
+    pub message: String,
+}
+
+impl From<DiagnosticDirective> for DriverError {
+    fn from(directive: DiagnosticDirective) -> Self {
+        Self {
+            code: ExitCode::DataErr,
+            msg: format!("line {}: #error {}", directive.line, directive.message),
+            ..Default::default()
         }
     }
+}
 
-    Ok(tokens)
+/// Checks `input` for `#error` and `#warning` directives.
+///
+/// Like [`expand_predefined_macros`], this is only needed when the source has not
+/// been through the preprocessor, which would otherwise have acted on them.
+/// Returns the first `#error` as an error. Otherwise returns every `#warning` for
+/// the caller to report, along with `input` with those lines blanked out so that it
+/// can be lexed. They are blanked with spaces, so spans still line up with `input`.
+pub fn check_diagnostic_directives(
+    input: &str,
+) -> Result<(Cow<'_, str>, Vec<DiagnosticDirective>), DiagnosticDirective> {
+    let mut warnings = Vec::new();
+    let mut output = String::new();
+    for (i, line) in input.split_inclusive('\n').enumerate() {
+        let Some(caps) = DIAGNOSTIC_DIRECTIVE.captures(line.trim_end_matches('\n')) else {
+            output.push_str(line);
+            continue;
+        };
+        let directive = DiagnosticDirective {
+            line: i + 1,
+            message: caps[2].to_string(),
+        };
+        if &caps[1] == "error" {
+            return Err(directive);
+        }
+        warnings.push(directive);
+        output.extend(line.chars().map(|c| if c == '\n' { c } else { ' ' }));
+    }
+
+    if warnings.is_empty() {
+        Ok((Cow::Borrowed(input), warnings))
+    } else {
+        Ok((Cow::Owned(output), warnings))
+    }
+}
+
+static LINEMARKER: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+    Regex::new(r#"^#[ \t]*([0-9]+)[ \t]+"((?:[^"\\]|\\.)*)"[ \t0-9]*\r?$"#).unwrap()
+});
+
+/// Where each line of preprocessed source came from, as recorded by the
+/// preprocessor's linemarkers. Made by [`strip_linemarkers`].
+#[derive(Debug, Default, PartialEq)]
+pub struct SourceLines {
+    /// Files that lines came from, in order of first appearance
+    pub files: Vec<String>,
+    /// Index into `files` and 1-based line number of each line of the preprocessed
+    /// source, or `None` for lines not from any file, like the linemarkers
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl SourceLines {
+    /// Returns the file, as an index into [`SourceLines::files`], and 1-based line
+    /// number that 1-based `line` of the preprocessed source came from.
+    pub fn locate(&self, line: usize) -> Option<(usize, usize)> {
+        self.lines.get(line.checked_sub(1)?).copied().flatten()
+    }
+}
+
+/// Removes the linemarkers, like `# 3 "main.c" 2`, that the preprocessor leaves in
+/// `input` to say where its lines came from, returning what they say.
+///
+/// Linemarkers are blanked with spaces, so spans still line up with `input`. If
+/// `input` has no linemarkers, which is the case with `gcc -E -P`, every line is
+/// located nowhere.
+pub fn strip_linemarkers(input: &str) -> (Cow<'_, str>, SourceLines) {
+    let mut source_lines = SourceLines::default();
+    let mut output = String::new();
+    // File index and line number of the next line
+    let mut next = None;
+    for line in input.split_inclusive('\n') {
+        let Some(caps) = LINEMARKER.captures(line.trim_end_matches('\n')) else {
+            output.push_str(line);
+            source_lines.lines.push(next);
+            if let Some((_, number)) = &mut next {
+                *number += 1;
+            }
+            continue;
+        };
+        // The file name is escaped like a string literal
+        let file = caps[2].replace("\\\"", "\"").replace("\\\\", "\\");
+        let index = match source_lines.files.iter().position(|f| *f == file) {
+            Some(index) => index,
+            None => {
+                source_lines.files.push(file);
+                source_lines.files.len() - 1
+            }
+        };
+        next = caps[1].parse().ok().map(|number| (index, number));
+        output.extend(line.chars().map(|c| if c == '\n' { c } else { ' ' }));
+        source_lines.lines.push(None);
+    }
+
+    if source_lines.files.is_empty() {
+        (Cow::Borrowed(input), source_lines)
+    } else {
+        (Cow::Owned(output), source_lines)
+    }
+}
+
+pub fn tokenize_str(input: &str) -> Result<Vec<Token>, LexError> {
+    let tokens = tokenize_str_with_spans(input)?;
+    Ok(tokens.into_iter().map(|(token, _)| token).collect())
+}
+
+/// Tokenizes `input`, pairing each token with its span in `input`.
+pub fn tokenize_str_with_spans(input: &str) -> Result<Vec<(Token, Span)>, LexError> {
+    Lexer::new().tokenize_str_with_spans(input)
+}
+
+/// Tokenizes `input` as the dialect described by `config`.
+///
+/// [`tokenize_str`] is this with [`LexerConfig::default`].
+pub fn tokenize_str_with(input: &str, config: &LexerConfig) -> Result<Vec<Token>, LexError> {
+    Lexer::new().with_config(config.clone()).tokenize_str(input)
+}
+
+/// Settings for lexing a dialect of C, for experimenting with the language.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexerConfig {
+    /// Whether `//` starts a comment running to the end of the line, as it does
+    /// since C99. On by default.
+    pub line_comments: bool,
+    /// Keywords to lex as identifiers instead, as spelled in the source
+    pub disabled_keywords: Vec<String>,
+    /// Extra words to lex as keywords, and the token each one is lexed as
+    pub extra_keywords: Vec<(String, Token)>,
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        Self {
+            line_comments: true,
+            disabled_keywords: Vec::new(),
+            extra_keywords: Vec::new(),
+        }
+    }
+}
+
+impl LexerConfig {
+    /// C89, which has no `//` comments
+    pub fn c89() -> Self {
+        Self {
+            line_comments: false,
+            ..Self::default()
+        }
+    }
+
+    /// Applies the keyword settings to `token`, which was lexed from `text`.
+    ///
+    /// Only tokens spelled exactly as the word are affected, so a rule added with
+    /// [`Lexer::with_rule`] can still make any name an identifier.
+    fn apply(&self, token: Token, text: &str) -> Token {
+        if token.is_keyword() && self.disabled_keywords.iter().any(|word| word == text) {
+            return Token::Identifier(text.to_string());
+        }
+        if matches!(&token, Token::Identifier(name) if name == text)
+            && let Some((_, keyword)) = self.extra_keywords.iter().find(|(word, _)| word == text)
+        {
+            return keyword.clone();
+        }
+        token
+    }
+}
+
+/// Default for [`Lexer::with_max_identifier_len`]
+pub const DEFAULT_MAX_IDENTIFIER_LEN: usize = 1024;
+
+/// A lexer using the built-in token rules, plus any added with [`Lexer::with_rule`].
+///
+/// The free functions like [`tokenize_str`] use only the built-in rules. This is
+/// for experimenting with extensions to the language.
+pub struct Lexer {
+    rules: Vec<LexerMapping>,
+    max_identifier_len: usize,
+    config: LexerConfig,
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            max_identifier_len: DEFAULT_MAX_IDENTIFIER_LEN,
+            config: LexerConfig::default(),
+        }
+    }
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lexes the dialect described by `config`, rather than [`LexerConfig::default`].
+    pub fn with_config(mut self, config: LexerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Makes identifiers longer than `max_len` characters an error, rather than
+    /// [`DEFAULT_MAX_IDENTIFIER_LEN`].
+    ///
+    /// Names are copied into every label and symbol made from them, so this keeps a
+    /// runaway name from blowing up the rest of compilation.
+    pub fn with_max_identifier_len(mut self, max_len: usize) -> Self {
+        self.max_identifier_len = max_len;
+        self
+    }
+
+    /// Adds a rule producing a token from each match of `regex`.
+    ///
+    /// Like the built-in rules, the longest match wins. Between matches of the same
+    /// length, the built-in rules win, then rules added earlier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `regex` does not follow the pattern `\A(<to capture>)`, which
+    /// anchors it to the start of the input.
+    pub fn with_rule(mut self, regex: Regex, to_token: fn(&str) -> Token) -> Self {
+        assert!(
+            regex.as_str().starts_with(r"\A("),
+            "lexer rules must match the start of the input"
+        );
+        self.rules.push((regex, to_token));
+        self
+    }
+
+    /// Tokenizes `input`.
+    pub fn tokenize_str(&self, input: &str) -> Result<Vec<Token>, LexError> {
+        let tokens = self.tokenize_str_with_spans(input)?;
+        Ok(tokens.into_iter().map(|(token, _)| token).collect())
+    }
+
+    /// Tokenizes `input`, pairing each token with its span in `input`.
+    ///
+    /// A UTF-8 byte order mark at the start of `input`, which some editors add, is
+    /// skipped.
+    pub fn tokenize_str_with_spans(&self, input: &str) -> Result<Vec<(Token, Span)>, LexError> {
+        let mut rest = input.strip_prefix(BYTE_ORDER_MARK).unwrap_or(input);
+        let mut tokens = Vec::new();
+
+        while !rest.is_empty() {
+            if rest.starts_with(char::is_whitespace) {
+                rest = rest.trim_start_matches(char::is_whitespace);
+            } else if self.config.line_comments && rest.starts_with("//") {
+                // The newline ending the comment is left as whitespace
+                rest = rest.find('\n').map_or("", |i| &rest[i..]);
+            } else {
+                let start = input.len() - rest.len();
+                let Some(token_info) = find_token(rest, LEXER_MAP.iter().chain(&self.rules)) else {
+                    // Unwrap is safe, as `rest` is not empty
+                    let len = rest.chars().next().unwrap().len_utf8();
+                    return Err(LexError {
+                        kind: LexErrorKind::InvalidToken,
+                        span: Span {
+                            start,
+                            end: start + len,
+                        },
+                    });
+                };
+                let len = cmp::min(token_info.len, rest.len());
+                let span = Span {
+                    start,
+                    end: start + len,
+                };
+                let token = self.config.apply(token_info.token, &rest[..len]);
+                if let Token::Identifier(name) = &token
+                    && name.chars().count() > self.max_identifier_len
+                {
+                    return Err(LexError {
+                        kind: LexErrorKind::IdentifierTooLong {
+                            max_len: self.max_identifier_len,
+                        },
+                        span,
+                    });
+                }
+                tokens.push((token, span));
+                rest = &rest[len..];
+            }
+        }
+
+        Ok(tokens)
+    }
+}
+
+/// Returns the identifiers in `tokens` longer than `max_len` characters, each once,
+/// in order of first appearance.
+///
+/// Implementations may ignore characters of an identifier past some limit, so this
+/// is useful to warn about names that may not be distinct elsewhere.
+pub fn long_identifiers<'a>(
+    tokens: impl IntoIterator<Item = &'a Token>,
+    max_len: usize,
+) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    for token in tokens {
+        if let Token::Identifier(name) = token
+            && name.chars().count() > max_len
+            && !names.contains(&name.as_str())
+        {
+            names.push(name.as_str());
+        }
+    }
+    names
+}
+
+/// Number of tokens of each kind in some input, from [`token_stats`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TokenStats {
+    /// Keywords, like `int` and `return`
+    pub keywords: usize,
+    pub identifiers: usize,
+    /// Integer constants
+    pub constants: usize,
+    pub string_literals: usize,
+    /// Punctuators, which includes every operator
+    pub punctuation: usize,
+    /// Tokens of every kind
+    pub total: usize,
+}
+
+/// Tokenizes `input`, and counts its tokens of each kind.
+pub fn token_stats(input: &str) -> Result<TokenStats, LexError> {
+    let mut stats = TokenStats::default();
+    for token in tokenize_str(input)? {
+        let count = match token {
+            Token::Identifier(_) => &mut stats.identifiers,
+            Token::Constant(_) => &mut stats.constants,
+            Token::StringLiteral(_) => &mut stats.string_literals,
+            _ if token.is_keyword() => &mut stats.keywords,
+            _ => &mut stats.punctuation,
+        };
+        *count += 1;
+        stats.total += 1;
+    }
+    Ok(stats)
 }
 
 type LexerMapping = (Regex, fn(&str) -> Token);
 
 /// Map for tokenizing. Maps from tokenizer regex to closure for generating the token from the
 /// regex capture.
-static LEXER_MAP: std::sync::LazyLock<[LexerMapping; 10]> = std::sync::LazyLock::new(lexer_map);
+static LEXER_MAP: std::sync::LazyLock<[LexerMapping; 49]> = std::sync::LazyLock::new(lexer_map);
 
 /// Produces the map to be used in `LEXER_MAP``.
 ///
@@ -75,28 +678,79 @@ static LEXER_MAP: std::sync::LazyLock<[LexerMapping; 10]> = std::sync::LazyLock:
 /// not require the capture.
 ///
 /// We need this function to work around unwraps not being allowed in static contexts.
-fn lexer_map() -> [LexerMapping; 10] {
+fn lexer_map() -> [LexerMapping; 49] {
     [
         (Regex::new(r"\A(int\b)").unwrap(), |_| Token::IntKeyword),
+        (Regex::new(r"\A(long\b)").unwrap(), |_| Token::LongKeyword),
+        (Regex::new(r"\A(char\b)").unwrap(), |_| Token::CharKeyword),
         (Regex::new(r"\A(void\b)").unwrap(), |_| Token::VoidKeyword),
         (Regex::new(r"\A(return\b)").unwrap(), |_| {
             Token::ReturnKeyword
         }),
+        (Regex::new(r"\A(if\b)").unwrap(), |_| Token::IfKeyword),
+        (Regex::new(r"\A(else\b)").unwrap(), |_| Token::ElseKeyword),
+        (Regex::new(r"\A(do\b)").unwrap(), |_| Token::DoKeyword),
+        (Regex::new(r"\A(while\b)").unwrap(), |_| Token::WhileKeyword),
+        (Regex::new(r"\A(for\b)").unwrap(), |_| Token::ForKeyword),
+        (Regex::new(r"\A(break\b)").unwrap(), |_| Token::BreakKeyword),
+        (Regex::new(r"\A(continue\b)").unwrap(), |_| {
+            Token::ContinueKeyword
+        }),
+        (Regex::new(r"\A(goto\b)").unwrap(), |_| Token::GotoKeyword),
+        (Regex::new(r"\A(switch\b)").unwrap(), |_| {
+            Token::SwitchKeyword
+        }),
+        (Regex::new(r"\A(case\b)").unwrap(), |_| Token::CaseKeyword),
+        (Regex::new(r"\A(default\b)").unwrap(), |_| {
+            Token::DefaultKeyword
+        }),
+        (Regex::new(r"\A(_Static_if\b)").unwrap(), |_| {
+            Token::StaticIfKeyword
+        }),
         (Regex::new(r"\A([a-zA-Z_]\w*\b)").unwrap(), |s| {
             Token::Identifier(s.to_owned())
         }),
         (Regex::new(r"\A([0-9]+\b)").unwrap(), |s| {
             Token::Constant(s.to_owned())
         }),
+        (Regex::new(r#"\A("([^"\\\n]|\\.)*")"#).unwrap(), |s| {
+            Token::StringLiteral(s[1..s.len() - 1].to_owned())
+        }),
         (Regex::new(r"\A(\()").unwrap(), |_| Token::OpenParenthesis),
         (Regex::new(r"\A(\))").unwrap(), |_| Token::CloseParenthesis),
         (Regex::new(r"\A(\{)").unwrap(), |_| Token::OpenBrace),
         (Regex::new(r"\A(\})").unwrap(), |_| Token::CloseBrace),
         (Regex::new(r"\A(;)").unwrap(), |_| Token::Semicolon),
+        (Regex::new(r"\A(,)").unwrap(), |_| Token::Comma),
+        (Regex::new(r"\A(\?)").unwrap(), |_| Token::QuestionMark),
+        (Regex::new(r"\A(:)").unwrap(), |_| Token::Colon),
+        (Regex::new(r"\A(\+)").unwrap(), |_| Token::Plus),
+        (Regex::new(r"\A(-)").unwrap(), |_| Token::Hyphen),
+        (Regex::new(r"\A(\*)").unwrap(), |_| Token::Asterisk),
+        (Regex::new(r"\A(/)").unwrap(), |_| Token::ForwardSlash),
+        (Regex::new(r"\A(%)").unwrap(), |_| Token::Percent),
+        (Regex::new(r"\A(&)").unwrap(), |_| Token::Ampersand),
+        (Regex::new(r"\A(\|)").unwrap(), |_| Token::Pipe),
+        (Regex::new(r"\A(\^)").unwrap(), |_| Token::Caret),
+        (Regex::new(r"\A(~)").unwrap(), |_| Token::Tilde),
+        (Regex::new(r"\A(!)").unwrap(), |_| Token::Exclamation),
+        (Regex::new(r"\A(<<)").unwrap(), |_| Token::DoubleLessThan),
+        (Regex::new(r"\A(>>)").unwrap(), |_| Token::DoubleGreaterThan),
+        (Regex::new(r"\A(&&)").unwrap(), |_| Token::DoubleAmpersand),
+        (Regex::new(r"\A(\|\|)").unwrap(), |_| Token::DoublePipe),
+        (Regex::new(r"\A(=)").unwrap(), |_| Token::Equal),
+        (Regex::new(r"\A(==)").unwrap(), |_| Token::DoubleEqual),
+        (Regex::new(r"\A(!=)").unwrap(), |_| Token::ExclamationEqual),
+        (Regex::new(r"\A(<)").unwrap(), |_| Token::LessThan),
+        (Regex::new(r"\A(<=)").unwrap(), |_| Token::LessThanEqual),
+        (Regex::new(r"\A(>)").unwrap(), |_| Token::GreaterThan),
+        (Regex::new(r"\A(>=)").unwrap(), |_| Token::GreaterThanEqual),
     ]
 }
 
-fn find_token(input: &str) -> Option<TokenInfo> {
+/// Finds the longest match of `rules` at the start of `input`, preferring earlier
+/// rules between matches of the same length.
+fn find_token<'r>(input: &str, rules: impl Iterator<Item = &'r LexerMapping>) -> Option<TokenInfo> {
     struct Match<'a> {
         match_: regex::Match<'a>,
         to_token: fn(&str) -> Token,
@@ -109,7 +763,7 @@ fn find_token(input: &str) -> Option<TokenInfo> {
     }
 
     let mut longest_match = None;
-    for (re, func) in &*LEXER_MAP {
+    for (re, func) in rules {
         let Some(match_) = re.find(input) else {
             continue;
         };
@@ -141,90 +795,136 @@ fn find_token(input: &str) -> Option<TokenInfo> {
 mod tests {
     use super::*;
 
+    fn find_builtin_token(input: &str) -> Option<TokenInfo> {
+        find_token(input, LEXER_MAP.iter())
+    }
+
     #[test]
     fn test_find_token() {
         use Token::*;
 
-        assert_eq!(find_token(""), None);
+        assert_eq!(find_builtin_token(""), None);
         assert_eq!(
-            find_token("int "),
+            find_builtin_token("int "),
             Some(TokenInfo {
                 token: IntKeyword,
                 len: 3
             })
         );
         assert_eq!(
-            find_token("int"),
+            find_builtin_token("int"),
             Some(TokenInfo {
                 token: IntKeyword,
                 len: 3
             })
         );
         assert_eq!(
-            find_token("my_var"),
+            find_builtin_token("my_var"),
             Some(TokenInfo {
                 token: Identifier("my_var".to_string()),
                 len: 6
             })
         );
         assert_eq!(
-            find_token("my_var2"),
+            find_builtin_token("my_var2"),
             Some(TokenInfo {
                 token: Identifier("my_var2".to_string()),
                 len: 7
             })
         );
-        assert_eq!(find_token("2my_var"), None); // Identifiers cannot start with numbers
+        assert_eq!(find_builtin_token("2my_var"), None); // Identifiers cannot start with numbers
+        assert_eq!(
+            find_builtin_token("long"),
+            Some(TokenInfo {
+                token: LongKeyword,
+                len: 4
+            })
+        );
         assert_eq!(
-            find_token("void"),
+            find_builtin_token("char"),
+            Some(TokenInfo {
+                token: CharKeyword,
+                len: 4
+            })
+        );
+        assert_eq!(
+            find_builtin_token("longer"),
+            Some(TokenInfo {
+                token: Identifier("longer".to_string()),
+                len: 6
+            })
+        );
+        assert_eq!(
+            find_builtin_token("void"),
             Some(TokenInfo {
                 token: VoidKeyword,
                 len: 4
             })
         );
         assert_eq!(
-            find_token("return"),
+            find_builtin_token("return"),
             Some(TokenInfo {
                 token: ReturnKeyword,
                 len: 6
             })
         );
         assert_eq!(
-            find_token("("),
+            find_builtin_token("if"),
+            Some(TokenInfo {
+                token: IfKeyword,
+                len: 2
+            })
+        );
+        assert_eq!(
+            find_builtin_token("else"),
+            Some(TokenInfo {
+                token: ElseKeyword,
+                len: 4
+            })
+        );
+        assert_eq!(
+            find_builtin_token("_Static_if"),
+            Some(TokenInfo {
+                token: StaticIfKeyword,
+                len: 10
+            })
+        );
+        assert_eq!(
+            find_builtin_token("("),
             Some(TokenInfo {
                 token: OpenParenthesis,
                 len: 1
             })
         );
         assert_eq!(
-            find_token(")"),
+            find_builtin_token(")"),
             Some(TokenInfo {
                 token: CloseParenthesis,
                 len: 1
             })
         );
         assert_eq!(
-            find_token("{"),
+            find_builtin_token("{"),
             Some(TokenInfo {
                 token: OpenBrace,
                 len: 1
             })
         );
         assert_eq!(
-            find_token("}"),
+            find_builtin_token("}"),
             Some(TokenInfo {
                 token: CloseBrace,
                 len: 1
             })
         );
         assert_eq!(
-            find_token("123"),
+            find_builtin_token("123"),
             Some(TokenInfo {
                 token: Constant("123".to_string()),
                 len: 3
             })
         );
-        assert_eq!(find_token("1_234"), None); // C, unlike some other languages, does not support underscores in integer literals
+        assert_eq!(find_builtin_token("1_234"), None); // C, unlike some other languages, does not support underscores in integer literals
     }
 
     #[test]
@@ -237,7 +937,7 @@ mod tests {
             Ok(vec![IntKeyword, Identifier("foo".to_string()), Semicolon])
         );
         assert_eq!(
-            tokenize_str("}()((99; foo int {;"),
+            tokenize_str("}()((99; foo int {;,"),
             Ok(vec![
                 CloseBrace,
                 OpenParenthesis,
@@ -249,7 +949,30 @@ mod tests {
                 Identifier("foo".to_string()),
                 IntKeyword,
                 OpenBrace,
-                Semicolon
+                Semicolon,
+                Comma
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_loop_keywords() {
+        use Token::*;
+
+        assert_eq!(
+            tokenize_str("do while for break continue goto switch case default doing gotos"),
+            Ok(vec![
+                DoKeyword,
+                WhileKeyword,
+                ForKeyword,
+                BreakKeyword,
+                ContinueKeyword,
+                GotoKeyword,
+                SwitchKeyword,
+                CaseKeyword,
+                DefaultKeyword,
+                Identifier("doing".to_string()),
+                Identifier("gotos".to_string())
             ])
         );
     }
@@ -260,4 +983,440 @@ mod tests {
 
         assert_eq!(tokenize_str("55555555555555555504"), Ok(vec![Constant("55555555555555555504".to_string())]));
     }
+
+    #[test]
+    fn test_replace_trigraphs() {
+        assert_eq!(replace_trigraphs("??("), "[");
+        assert_eq!(
+            replace_trigraphs("??=define X ??< ??> ??)"),
+            "#define X { } ]"
+        );
+        assert_eq!(replace_trigraphs("??/ ??' ??! ??-"), "\\ ^ | ~");
+        assert_eq!(replace_trigraphs("???="), "?#");
+        assert_eq!(replace_trigraphs("?? ?"), "?? ?");
+        assert!(matches!(replace_trigraphs("int main"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_expand_predefined_macros() {
+        use Token::*;
+
+        let expand = |input: &str, path: &str| {
+            let mut tokens = tokenize_str_with_spans(input).unwrap();
+            expand_predefined_macros(&mut tokens, input, path);
+            tokens
+                .into_iter()
+                .map(|(token, _)| token)
+                .collect::<Vec<_>>()
+        };
+
+        let source = "int main(void) {\n    int x = 0;\n    return __LINE__;\n}\n";
+        assert_eq!(expand(source, "main.c")[12], Constant("3".to_string()));
+        assert_eq!(
+            expand("__FILE__", "dir/main.c"),
+            [StringLiteral("dir/main.c".to_string())]
+        );
+        assert_eq!(
+            expand("__FILE__", r#"a "b"\c.c"#),
+            [StringLiteral(r#"a \"b\"\\c.c"#.to_string())]
+        );
+        // Only whole tokens are expanded, outside of string literals and comments
+        assert_eq!(
+            expand("x__LINE__ \"__FILE__\" // __LINE__\n__FILE", "main.c"),
+            [
+                Identifier("x__LINE__".to_string()),
+                StringLiteral("__FILE__".to_string()),
+                Identifier("__FILE".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_diagnostic_directives() {
+        let input = "#warning careful\nint main(void) {\n  #  warning  again \n}\n";
+        let (output, warnings) = check_diagnostic_directives(input).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                DiagnosticDirective {
+                    line: 1,
+                    message: "careful".to_string()
+                },
+                DiagnosticDirective {
+                    line: 3,
+                    message: "again".to_string()
+                },
+            ]
+        );
+        // The directives are blanked out, keeping every other byte where it was
+        assert_eq!(output.len(), input.len());
+        assert_eq!(tokenize_str(&output), tokenize_str("int main(void) {\n}\n"));
+
+        let err = check_diagnostic_directives("#warning careful\n#error stop\n#error later\n")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            DiagnosticDirective {
+                line: 2,
+                message: "stop".to_string()
+            }
+        );
+        assert_eq!(DriverError::from(err).msg, "line 2: #error stop");
+
+        let input = "#errors\nint x = 1; # error\n#define warning\n";
+        assert!(matches!(
+            check_diagnostic_directives(input),
+            Ok((Cow::Borrowed(_), warnings)) if warnings.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_strip_linemarkers() {
+        let input = concat!(
+            "# 0 \"main.c\"\n",
+            "# 1 \"dir/a \\\"b\\\".h\" 1\n",
+            "int f(void);\n",
+            "# 3 \"main.c\" 2\n",
+            "\n",
+            "int main(void) { return 0; }\n",
+        );
+        let (output, source_lines) = strip_linemarkers(input);
+        assert_eq!(output.len(), input.len());
+        assert_eq!(
+            tokenize_str(&output),
+            tokenize_str("int f(void); int main(void) { return 0; }")
+        );
+        assert_eq!(source_lines.files, ["main.c", "dir/a \"b\".h"]);
+        assert_eq!(source_lines.locate(1), None);
+        assert_eq!(source_lines.locate(3), Some((1, 1)));
+        assert_eq!(source_lines.locate(6), Some((0, 4)));
+        assert_eq!(source_lines.locate(7), None);
+        assert_eq!(source_lines.locate(0), None);
+
+        // Without linemarkers, as with `gcc -E -P`, lines aren't located
+        let (output, source_lines) = strip_linemarkers("int x;\n# define\n");
+        assert!(matches!(output, Cow::Borrowed(_)));
+        assert_eq!(source_lines.locate(1), None);
+    }
+
+    #[test]
+    fn test_long_identifiers() {
+        let tokens =
+            tokenize_str("int averylongidentifier = 1; short = averylongidentifier + eightchr;")
+                .unwrap();
+        assert_eq!(long_identifiers(&tokens, 8), ["averylongidentifier"]);
+        assert!(long_identifiers(&tokens, 19).is_empty());
+
+        // Characters are counted, not bytes
+        let tokens = tokenize_str("héllo").unwrap();
+        assert!(long_identifiers(&tokens, 5).is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_str_with_spans() {
+        use Token::*;
+
+        let input = "int main\n  ( ) ;";
+        let tokens = tokenize_str_with_spans(input).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                (IntKeyword, Span { start: 0, end: 3 }),
+                (Identifier("main".to_string()), Span { start: 4, end: 8 }),
+                (OpenParenthesis, Span { start: 11, end: 12 }),
+                (CloseParenthesis, Span { start: 13, end: 14 }),
+                (Semicolon, Span { start: 15, end: 16 }),
+            ]
+        );
+        assert_eq!(tokens[0].1.line_column(input), (1, 1));
+        assert_eq!(tokens[1].1.line_column(input), (1, 5));
+        assert_eq!(tokens[4].1.line_column(input), (2, 7));
+    }
+
+    #[test]
+    fn test_tokenize_line_endings() {
+        use Token::*;
+
+        // `\r\n` ends one line, and its `\r` is whitespace like any other
+        let input = "int\r\nmain";
+        let tokens = tokenize_str_with_spans(input).unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[1].0, Identifier("main".to_string()));
+        assert_eq!(tokens[1].1.line_column(input), (2, 1));
+
+        // Mixed line endings, and no newline at the end of the file
+        let input = "int main(void)\r\n{\n  return 0;\r\n}";
+        let tokens = tokenize_str_with_spans(input).unwrap();
+        assert_eq!(tokens.len(), 10);
+        let (last, span) = &tokens[9];
+        assert_eq!(*last, CloseBrace);
+        assert_eq!(span.line_column(input), (4, 1));
+        assert_eq!(tokens[6].1.line_column(input), (3, 3));
+    }
+
+    #[test]
+    fn test_tokenize_byte_order_mark() {
+        let input = "int main(void) { return 0; }";
+        let with_bom = format!("\u{FEFF}{input}");
+        assert_eq!(tokenize_str(&with_bom), tokenize_str(input));
+
+        // Spans are still into the input, but columns don't count the mark
+        let tokens = tokenize_str_with_spans(&with_bom).unwrap();
+        assert_eq!(tokens[0].1, Span { start: 3, end: 6 });
+        assert_eq!(tokens[0].1.line_column(&with_bom), (1, 1));
+
+        // Anywhere else, it is an invalid token
+        assert_eq!(
+            tokenize_str("int \u{FEFF}"),
+            Err(LexError {
+                kind: LexErrorKind::InvalidToken,
+                span: Span { start: 4, end: 7 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_tokenize_max_identifier_len() {
+        let name = "a".repeat(DEFAULT_MAX_IDENTIFIER_LEN);
+        assert_eq!(
+            tokenize_str(&format!("int {name};")),
+            Ok(vec![
+                Token::IntKeyword,
+                Token::Identifier(name.clone()),
+                Token::Semicolon
+            ])
+        );
+
+        let input = format!("int {name}b;");
+        let err = tokenize_str(&input).unwrap_err();
+        assert_eq!(
+            err,
+            LexError {
+                kind: LexErrorKind::IdentifierTooLong {
+                    max_len: DEFAULT_MAX_IDENTIFIER_LEN
+                },
+                span: Span {
+                    start: 4,
+                    end: input.len() - 1
+                },
+            }
+        );
+        assert_eq!(
+            DriverError::from(err).msg,
+            "Failed to lex: identifier is longer than the limit of 1024 characters."
+        );
+
+        // Keywords aren't identifiers, so they can't be too long
+        let lexer = Lexer::new().with_max_identifier_len(3);
+        assert_eq!(
+            lexer.tokenize_str("return abc;"),
+            Ok(vec![
+                Token::ReturnKeyword,
+                Token::Identifier("abc".to_string()),
+                Token::Semicolon
+            ])
+        );
+        assert_eq!(
+            lexer.tokenize_str("abcd").unwrap_err().kind,
+            LexErrorKind::IdentifierTooLong { max_len: 3 }
+        );
+    }
+
+    #[test]
+    fn test_token_stats() {
+        let input = "int main(void) {
+            int x = 10;
+            while (x > 0) x = x - 1;
+            return x;
+        }";
+        assert_eq!(
+            token_stats(input),
+            Ok(TokenStats {
+                keywords: 5,
+                identifiers: 6,
+                constants: 3,
+                string_literals: 0,
+                punctuation: 13,
+                total: 27,
+            })
+        );
+        assert_eq!(token_stats(""), Ok(TokenStats::default()));
+        assert!(token_stats("int @").is_err());
+    }
+
+    #[test]
+    fn test_lexer_with_rule() {
+        use Token::*;
+
+        // `@` makes any name an identifier, even a keyword, and `@i` is short for `int`
+        let lexer = Lexer::new()
+            .with_rule(Regex::new(r"\A(@[a-zA-Z_]\w*\b)").unwrap(), |s| {
+                Identifier(s[1..].to_owned())
+            })
+            .with_rule(Regex::new(r"\A(@i)").unwrap(), |_| IntKeyword);
+        assert_eq!(
+            lexer.tokenize_str("int @int = @if; @i x;"),
+            Ok(vec![
+                IntKeyword,
+                Identifier("int".to_string()),
+                Equal,
+                Identifier("if".to_string()),
+                Semicolon,
+                // Both rules match `@i`, so the earlier one wins
+                Identifier("i".to_string()),
+                Identifier("x".to_string()),
+                Semicolon,
+            ])
+        );
+        // The built-in rules still lex as before
+        assert_eq!(lexer.tokenize_str("a == b"), tokenize_str("a == b"));
+        assert!(tokenize_str("@int").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_str_with() {
+        use Token::*;
+
+        let input = "x = 1; // x = 2;\nreturn x;";
+        let expected = vec![
+            Identifier("x".to_string()),
+            Equal,
+            Constant("1".to_string()),
+            Semicolon,
+            ReturnKeyword,
+            Identifier("x".to_string()),
+            Semicolon,
+        ];
+        assert_eq!(tokenize_str(input), Ok(expected.clone()));
+        assert_eq!(
+            tokenize_str_with(input, &LexerConfig::default()),
+            Ok(expected)
+        );
+        // A comment can end the input
+        assert_eq!(
+            tokenize_str("return; //"),
+            Ok(vec![ReturnKeyword, Semicolon])
+        );
+
+        // Without line comments, `//` is two slashes
+        assert_eq!(
+            tokenize_str_with("a // b", &LexerConfig::c89()),
+            Ok(vec![
+                Identifier("a".to_string()),
+                ForwardSlash,
+                ForwardSlash,
+                Identifier("b".to_string())
+            ])
+        );
+
+        let config = LexerConfig {
+            disabled_keywords: vec!["for".to_string()],
+            extra_keywords: vec![("unless".to_string(), IfKeyword)],
+            ..LexerConfig::default()
+        };
+        assert_eq!(
+            tokenize_str_with("for while unless fortune", &config),
+            Ok(vec![
+                Identifier("for".to_string()),
+                WhileKeyword,
+                IfKeyword,
+                Identifier("fortune".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "lexer rules must match the start of the input")]
+    fn test_lexer_with_unanchored_rule() {
+        let _ = Lexer::new().with_rule(Regex::new("@").unwrap(), |_| Token::Tilde);
+    }
+
+    #[test]
+    fn test_render_lex_error() {
+        let input = "int main(void) {\n    return 1 @ 2;\n}\n";
+        let err = tokenize_str_with_spans(input).unwrap_err();
+        assert_eq!(err.span, Span { start: 30, end: 31 });
+
+        let err = DriverError {
+            path: Some("main.c".to_string()),
+            ..err.into()
+        };
+        assert_eq!(
+            err.render(input),
+            "Failed to lex: invalid token.
+ --> main.c:2:14
+  |
+2 |     return 1 @ 2;
+  |              ^"
+        );
+
+        // The span covers the whole of a multi-byte character
+        let err = tokenize_str_with_spans("int é;").unwrap_err();
+        assert_eq!(err.span, Span { start: 4, end: 6 });
+    }
+
+    #[test]
+    fn test_tokenize_trigraphs() {
+        use Token::*;
+
+        // Without trigraph replacement, the sequence is just question marks and a parenthesis
+        assert_eq!(
+            tokenize_str("??("),
+            Ok(vec![QuestionMark, QuestionMark, OpenParenthesis])
+        );
+        assert_eq!(
+            tokenize_str(&replace_trigraphs("int main(void) ??< return 0; ??>")),
+            tokenize_str("int main(void) { return 0; }")
+        );
+    }
+
+    #[test]
+    fn test_tokenize_operators() {
+        use Token::*;
+
+        assert_eq!(
+            tokenize_str("+ - * / % & | ^ << >> && || = == != < <= > >= ? :"),
+            Ok(vec![
+                Plus,
+                Hyphen,
+                Asterisk,
+                ForwardSlash,
+                Percent,
+                Ampersand,
+                Pipe,
+                Caret,
+                DoubleLessThan,
+                DoubleGreaterThan,
+                DoubleAmpersand,
+                DoublePipe,
+                Equal,
+                DoubleEqual,
+                ExclamationEqual,
+                LessThan,
+                LessThanEqual,
+                GreaterThan,
+                GreaterThanEqual,
+                QuestionMark,
+                Colon
+            ])
+        );
+        // Longest match wins without needing whitespace between operators
+        assert_eq!(
+            tokenize_str("~!a<<=b&&&c!=!d"),
+            Ok(vec![
+                Tilde,
+                Exclamation,
+                Identifier("a".to_string()),
+                DoubleLessThan,
+                Equal,
+                Identifier("b".to_string()),
+                DoubleAmpersand,
+                Ampersand,
+                Identifier("c".to_string()),
+                ExclamationEqual,
+                Exclamation,
+                Identifier("d".to_string())
+            ])
+        );
+    }
 }
@@ -0,0 +1,235 @@
+//! Abstract syntax tree produced by the parser.
+//!
+//! With the `serde` feature enabled, every node can be (de)serialized. Each
+//! serialized node carries a `type` tag naming the node, alongside its fields.
+
+use std::fmt;
+
+use lexer::Span;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub struct Program {
+    pub declarations: Vec<Declaration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub struct FunctionDefinition {
+    pub name: String,
+    /// Parameter types and names. Names may only be omitted when there is no body.
+    pub params: Vec<(Type, Option<String>)>,
+    /// Whether the parameters are declared, as they are for `f(void)` but not for `f()`.
+    /// Calls to a function without a prototype may pass any number of arguments.
+    pub has_prototype: bool,
+    /// Always a [`Statement::Compound`], or `None` if this only declares the function
+    pub body: Option<Statement>,
+    /// Span of the function's name, if parsed with [`parse_with_spans`](crate::parse_with_spans)
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub span: Option<Span>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum BlockItem {
+    Statement(Statement),
+    Declaration(Declaration),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Declaration {
+    Variable(VariableDeclaration),
+    Function(Box<FunctionDefinition>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub struct VariableDeclaration {
+    pub name: String,
+    pub init: Option<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Statement {
+    Return(Expression),
+    Expression(Expression),
+    If {
+        condition: Expression,
+        then: Box<Statement>,
+        otherwise: Option<Box<Statement>>,
+    },
+    Compound(Vec<BlockItem>),
+    Break {
+        /// Label of the loop this breaks out of, once loops are labeled
+        label: Option<String>,
+    },
+    Continue {
+        /// Label of the loop this continues, once loops are labeled
+        label: Option<String>,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+        /// Unique label of the loop, once loops are labeled
+        label: Option<String>,
+    },
+    DoWhile {
+        body: Box<Statement>,
+        condition: Expression,
+        /// Unique label of the loop, once loops are labeled
+        label: Option<String>,
+    },
+    For {
+        init: ForInit,
+        condition: Option<Expression>,
+        post: Option<Expression>,
+        body: Box<Statement>,
+        /// Unique label of the loop, once loops are labeled
+        label: Option<String>,
+    },
+    Switch {
+        control: Expression,
+        body: Box<Statement>,
+        /// Unique label of the switch, once switches are labeled
+        label: Option<String>,
+        /// Value (`None` for `default`) and label of each case in the body, once
+        /// switches are labeled
+        cases: Vec<(Option<i64>, String)>,
+    },
+    /// A statement `switch` jumps to when its control expression equals `value`
+    Case {
+        /// The case's constant, converted to `int` like the control expression
+        value: i64,
+        stmt: Box<Statement>,
+        /// Unique label of the case, once switches are labeled
+        label: Option<String>,
+    },
+    /// A statement `switch` jumps to when no case matches
+    Default {
+        stmt: Box<Statement>,
+        /// Unique label of the default, once switches are labeled
+        label: Option<String>,
+    },
+    /// Jump to the statement with this label, which is unique to the function once
+    /// labels are resolved
+    Goto(String),
+    /// A statement with a label `goto` can jump to
+    Labeled {
+        name: String,
+        stmt: Box<Statement>,
+    },
+    Null,
+}
+
+/// The first clause of a `for` loop header.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum ForInit {
+    Declaration(VariableDeclaration),
+    Expression(Option<Expression>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Expression {
+    Constant(i64),
+    Var(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expression>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Assignment {
+        target: Box<Expression>,
+        value: Box<Expression>,
+    },
+    Conditional {
+        cond: Box<Expression>,
+        then: Box<Expression>,
+        otherwise: Box<Expression>,
+    },
+    Cast {
+        ty: Type,
+        expr: Box<Expression>,
+    },
+    FunctionCall {
+        name: String,
+        args: Vec<Expression>,
+    },
+    /// `__builtin_expect(expr, expected)`, which evaluates to `expr`. `expected` is
+    /// the value `expr` is likely to have, as a hint for branch layout.
+    Expect {
+        expr: Box<Expression>,
+        expected: Box<Expression>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Type {
+    Char,
+    Int,
+    Long,
+    Pointer(Box<Type>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Char => write!(f, "char"),
+            Type::Int => write!(f, "int"),
+            Type::Long => write!(f, "long"),
+            Type::Pointer(referenced) => write!(f, "{referenced}*"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum UnaryOp {
+    Negate,
+    Complement,
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+    And,
+    Or,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+    /// Evaluates the left operand for its side effects, then the right for the value
+    Comma,
+}
@@ -0,0 +1,140 @@
+//! Compile-time evaluation of integer constant expressions.
+
+use crate::ast::{BinaryOp, Expression, Type, UnaryOp};
+
+/// Evaluates `exp` as an integer constant expression.
+///
+/// Returns `None` if `exp` is not a constant expression (e.g. it refers to a
+/// variable or performs an assignment), or if evaluating it would be undefined
+/// (division by zero, overflow, or an out-of-range shift).
+///
+/// Arithmetic is done in 64 bits. Casts truncate to the width of the target type.
+pub fn const_eval(exp: &Expression) -> Option<i64> {
+    match exp {
+        Expression::Constant(value) => Some(*value),
+        Expression::Var(_) | Expression::Assignment { .. } | Expression::FunctionCall { .. } => {
+            None
+        }
+        // Like gcc, folded when both arguments are constant
+        Expression::Expect { expr, expected } => {
+            const_eval(expected)?;
+            const_eval(expr)
+        }
+        Expression::Unary { op, expr } => {
+            let value = const_eval(expr)?;
+            match op {
+                UnaryOp::Negate => value.checked_neg(),
+                UnaryOp::Complement => Some(!value),
+                UnaryOp::Not => Some((value == 0).into()),
+            }
+        }
+        // The logical operators short-circuit, so the right operand need not be a
+        // valid constant when the left one decides the result
+        Expression::Binary {
+            op: BinaryOp::And,
+            left,
+            right,
+        } => {
+            if const_eval(left)? == 0 {
+                Some(0)
+            } else {
+                Some((const_eval(right)? != 0).into())
+            }
+        }
+        Expression::Binary {
+            op: BinaryOp::Or,
+            left,
+            right,
+        } => {
+            if const_eval(left)? != 0 {
+                Some(1)
+            } else {
+                Some((const_eval(right)? != 0).into())
+            }
+        }
+        // Constant expressions may not contain the comma operator
+        Expression::Binary {
+            op: BinaryOp::Comma,
+            ..
+        } => None,
+        Expression::Binary { op, left, right } => {
+            let left = const_eval(left)?;
+            let right = const_eval(right)?;
+            match op {
+                BinaryOp::Add => left.checked_add(right),
+                BinaryOp::Subtract => left.checked_sub(right),
+                BinaryOp::Multiply => left.checked_mul(right),
+                BinaryOp::Divide => left.checked_div(right),
+                BinaryOp::Remainder => left.checked_rem(right),
+                BinaryOp::BitwiseAnd => Some(left & right),
+                BinaryOp::BitwiseOr => Some(left | right),
+                BinaryOp::BitwiseXor => Some(left ^ right),
+                BinaryOp::LeftShift => left.checked_shl(u32::try_from(right).ok()?),
+                BinaryOp::RightShift => left.checked_shr(u32::try_from(right).ok()?),
+                BinaryOp::Equal => Some((left == right).into()),
+                BinaryOp::NotEqual => Some((left != right).into()),
+                BinaryOp::LessThan => Some((left < right).into()),
+                BinaryOp::LessOrEqual => Some((left <= right).into()),
+                BinaryOp::GreaterThan => Some((left > right).into()),
+                BinaryOp::GreaterOrEqual => Some((left >= right).into()),
+                // Handled above
+                BinaryOp::And | BinaryOp::Or | BinaryOp::Comma => unreachable!(),
+            }
+        }
+        Expression::Conditional {
+            cond,
+            then,
+            otherwise,
+        } => {
+            if const_eval(cond)? != 0 {
+                const_eval(then)
+            } else {
+                const_eval(otherwise)
+            }
+        }
+        Expression::Cast { ty, expr } => {
+            let value = const_eval(expr)?;
+            match ty {
+                Type::Char => Some(value as i8 as i64),
+                Type::Int => Some(value as i32 as i64),
+                Type::Long => Some(value),
+                // Addresses are not integer constants
+                Type::Pointer(_) => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(input: &str) -> Option<i64> {
+        let mut tokens = crate::TokenCursor::new(lexer::tokenize_str(input).unwrap());
+        const_eval(&crate::parse_expression(&mut tokens, 0).unwrap())
+    }
+
+    #[test]
+    fn test_const_eval() {
+        assert_eq!(eval_str("1 + 2 * 3"), Some(7));
+        assert_eq!(eval_str("-(7 / 2) % 3"), Some(0));
+        assert_eq!(eval_str("~0 & 255"), Some(255));
+        assert_eq!(eval_str("1 << 4 | 1"), Some(17));
+        assert_eq!(eval_str("!5 == 0 ? 10 : 20"), Some(10));
+        assert_eq!(eval_str("(char)300"), Some(44));
+        assert_eq!(eval_str("0 && 1 / 0"), Some(0));
+        assert_eq!(eval_str("1 || x"), Some(1));
+        assert_eq!(eval_str("__builtin_expect(3, 1)"), Some(3));
+    }
+
+    #[test]
+    fn test_const_eval_not_constant() {
+        assert_eq!(eval_str("x + 1"), None);
+        assert_eq!(eval_str("x = 1"), None);
+        assert_eq!(eval_str("1 / 0"), None);
+        assert_eq!(eval_str("1 << 64"), None);
+        assert_eq!(eval_str("1 && x"), None);
+        assert_eq!(eval_str("(1, 2)"), None);
+        assert_eq!(eval_str("__builtin_expect(3, x)"), None);
+    }
+}
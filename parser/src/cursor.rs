@@ -0,0 +1,178 @@
+//! The token stream a parser reads from.
+
+use std::vec::IntoIter;
+
+use lexer::{Span, Token};
+
+use crate::ParseError;
+
+/// A cursor over a token stream, with as much lookahead as a parser needs.
+///
+/// This counts the tokens consumed, so that an error can be located at the last
+/// token consumed, which is the one that caused it. It also tracks which `(` and `{`
+/// are still open, so running out of tokens can be blamed on the innermost one.
+pub struct TokenCursor {
+    tokens: IntoIter<Token>,
+    consumed: usize,
+    /// Span of each token, or empty if they aren't known
+    spans: Vec<Span>,
+    /// Indices of the open delimiters consumed so far, innermost last
+    open_delimiters: Vec<usize>,
+}
+
+impl TokenCursor {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens: tokens.into_iter(),
+            consumed: 0,
+            spans: Vec::new(),
+            open_delimiters: Vec::new(),
+        }
+    }
+
+    /// Makes a cursor over `tokens` that knows where each one is, so that it can
+    /// report [`ParseError::UnclosedDelimiter`].
+    pub fn with_spans(tokens: Vec<(Token, Span)>) -> Self {
+        let (tokens, spans) = tokens.into_iter().unzip();
+        Self {
+            spans,
+            ..Self::new(tokens)
+        }
+    }
+
+    /// Span of the `index`th token, if spans are known
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.spans.get(index).copied()
+    }
+
+    /// Number of tokens consumed so far
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Returns the next token, without consuming it
+    pub fn peek(&self) -> Option<&Token> {
+        self.peek_n(0)
+    }
+
+    /// Looks `n` tokens past the next one, without consuming anything
+    pub fn peek_n(&self, n: usize) -> Option<&Token> {
+        self.tokens.as_slice().get(n)
+    }
+
+    /// Consumes the next token
+    pub fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.next()?;
+        match token {
+            Token::OpenParenthesis | Token::OpenBrace => self.open_delimiters.push(self.consumed),
+            Token::CloseParenthesis | Token::CloseBrace => {
+                self.open_delimiters.pop();
+            }
+            _ => {}
+        }
+        self.consumed += 1;
+        Some(token)
+    }
+
+    /// Returns the error for a delimiter left open at the end of the tokens, if any
+    /// is and spans are known.
+    pub fn unclosed_delimiter(&self) -> Option<ParseError> {
+        let open_at = self.span(*self.open_delimiters.last()?)?;
+        Some(ParseError::UnclosedDelimiter { open_at })
+    }
+
+    /// Consumes the next token if `func` accepts it
+    pub fn next_if(&mut self, func: impl FnOnce(&Token) -> bool) -> Option<Token> {
+        if func(self.peek()?) {
+            self.advance()
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the next token if it is `expected`
+    pub fn next_if_eq(&mut self, expected: &Token) -> Option<Token> {
+        self.next_if(|token| token == expected)
+    }
+
+    /// Consumes the next token, erroring if the token stream has been exhausted.
+    pub fn advance_or_eof(&mut self) -> Result<Token, ParseError> {
+        self.advance().ok_or_else(|| {
+            self.unclosed_delimiter()
+                .unwrap_or(ParseError::UnexpectedEof)
+        })
+    }
+
+    /// Consumes the next token, erroring if it is not `expected`.
+    pub fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        let token = self.advance_or_eof()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedToken {
+                expected: expected.to_string(),
+                found: Some(token),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(input: &str) -> TokenCursor {
+        TokenCursor::new(lexer::tokenize_str(input).unwrap())
+    }
+
+    #[test]
+    fn test_peek_n() {
+        let mut tokens = cursor("a : b");
+        assert_eq!(tokens.peek(), Some(&Token::Identifier("a".to_string())));
+        assert_eq!(tokens.peek_n(1), Some(&Token::Colon));
+        assert_eq!(tokens.peek_n(3), None);
+        assert_eq!(tokens.consumed(), 0);
+
+        tokens.advance();
+        assert_eq!(tokens.peek_n(1), Some(&Token::Identifier("b".to_string())));
+        assert_eq!(tokens.consumed(), 1);
+    }
+
+    #[test]
+    fn test_unclosed_delimiter() {
+        let input = "f({ (x) ";
+        let mut tokens = TokenCursor::with_spans(lexer::tokenize_str_with_spans(input).unwrap());
+        while tokens.advance().is_some() {}
+        assert_eq!(
+            tokens.advance_or_eof(),
+            Err(ParseError::UnclosedDelimiter {
+                open_at: Span { start: 2, end: 3 }
+            })
+        );
+
+        // Without spans, there is nowhere to point
+        let mut tokens = cursor(input);
+        while tokens.advance().is_some() {}
+        assert_eq!(tokens.unclosed_delimiter(), None);
+        assert_eq!(tokens.advance_or_eof(), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_expect() {
+        let mut tokens = cursor("( ;");
+        assert_eq!(tokens.expect(Token::OpenParenthesis), Ok(()));
+        assert_eq!(
+            tokens.expect(Token::CloseParenthesis),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)`".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+        // The wrong token is still consumed, so errors point at it
+        assert_eq!(tokens.consumed(), 2);
+        assert_eq!(
+            tokens.expect(Token::Semicolon),
+            Err(ParseError::UnexpectedEof)
+        );
+    }
+}
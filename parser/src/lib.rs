@@ -1,6 +1,2013 @@
-pub fn parse() {}
+use std::fmt;
+
+use errors::{DriverError, ExitCode};
+use lexer::{Span, Token};
+
+pub mod ast;
+pub mod const_eval;
+pub mod cursor;
+pub mod pretty;
+
+use ast::{
+    BinaryOp, BlockItem, Declaration, Expression, ForInit, FunctionDefinition, Program, Statement,
+    Type, UnaryOp, VariableDeclaration,
+};
+use const_eval::const_eval;
+use cursor::TokenCursor;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// A token other than the one the grammar requires was encountered
+    UnexpectedToken {
+        /// Description of what the grammar required at this point
+        expected: String,
+        /// The token that was found instead, if any
+        found: Option<Token>,
+    },
+    /// The token stream ended part way through a construct
+    UnexpectedEof,
+    /// An expression that must be evaluated at compile time is not an integer constant
+    NotConstant,
+    /// A function definition has a parameter without a name
+    UnnamedParameter {
+        /// Name of the function being defined
+        function: String,
+    },
+    /// An integer constant is too large for any integer type
+    IntegerTooLarge {
+        /// The constant, as written
+        value: String,
+    },
+    /// The tokens ended while a `(` or `{` was still open. Only reported when the
+    /// spans of the tokens are known.
+    UnclosedDelimiter {
+        /// Span of the innermost delimiter left open
+        open_at: Span,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken {
+                expected,
+                found: Some(found),
+            } => write!(f, "expected {expected}, found {found}"),
+            ParseError::UnexpectedToken {
+                expected,
+                found: None,
+            } => write!(f, "expected {expected}, found end of file"),
+            ParseError::UnexpectedEof => write!(f, "unexpected end of file"),
+            ParseError::NotConstant => write!(f, "expected integer constant expression"),
+            ParseError::UnnamedParameter { function } => {
+                write!(f, "parameter name omitted in definition of `{function}`")
+            }
+            ParseError::IntegerTooLarge { value } => {
+                write!(
+                    f,
+                    "integer constant `{value}` is too large for any integer type"
+                )
+            }
+            ParseError::UnclosedDelimiter { .. } => write!(f, "unclosed delimiter"),
+        }
+    }
+}
+
+impl From<ParseError> for DriverError {
+    fn from(e: ParseError) -> Self {
+        Self {
+            code: ExitCode::DataErr,
+            msg: format!("Failed to parse: {e}."),
+            ..Default::default()
+        }
+    }
+}
+
+/// A [`ParseError`] along with where it was found
+#[derive(Debug, PartialEq)]
+pub struct SpannedParseError {
+    pub error: ParseError,
+    /// Span of the token the error was found at, or `None` if there were no tokens.
+    /// At the end of the token stream, this is the last token.
+    pub span: Option<Span>,
+}
+
+impl SpannedParseError {
+    /// Converts this into a [`DriverError`] whose message gives the line and column
+    /// of the error in `source`, the input that was tokenized.
+    pub fn into_driver_error(self, source: &str) -> DriverError {
+        let Some(span) = self.span else {
+            return self.error.into();
+        };
+        let (line, column) = span.line_column(source);
+        DriverError {
+            code: ExitCode::DataErr,
+            msg: format!(
+                "Failed to parse: {} at line {line}, column {column}.",
+                self.error
+            ),
+            span: Some((span.start, span.end)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parses a token stream into a [`Program`].
+///
+/// The grammar currently supported is:
+/// ```text
+/// <program> ::= { <file-scope-item> }
+/// <file-scope-item> ::= <function-declaration>
+///                     | "_Static_if" "(" <exp> ")" "{" { <file-scope-item> } "}"
+/// <declaration> ::= <variable-declaration> | <function-declaration>
+/// <variable-declaration> ::= "int" <identifier> [ "=" <exp> ] ";"
+/// <function-declaration> ::= "int" <identifier> "(" <param-list> ")" ( <block> | ";" )
+/// <param-list> ::= "void" | [ <type-name> [ <identifier> ] { "," <type-name> [ <identifier> ] } ]
+/// <block> ::= "{" { <block-item> } "}"
+/// <block-item> ::= <statement> | <declaration> | "_Static_if" "(" <exp> ")" <block>
+/// <statement> ::= "return" <exp> ";" | <exp> ";"
+///               | "if" "(" <exp> ")" <statement> [ "else" <statement> ] | <block>
+///               | "break" ";" | "continue" ";" | "while" "(" <exp> ")" <statement>
+///               | "do" <statement> "while" "(" <exp> ")" ";"
+///               | "for" "(" <for-init> [ <exp> ] ";" [ <exp> ] ")" <statement> | ";"
+///               | "goto" <identifier> ";" | <identifier> ":" <statement>
+///               | "switch" "(" <exp> ")" <statement> | "case" <exp> ":" <statement>
+///               | "default" ":" <statement>
+/// <for-init> ::= <variable-declaration> | [ <exp> ] ";"
+/// <exp> ::= <factor> | <exp> <binop> <exp> | <exp> "?" <exp> ":" <exp>
+/// <factor> ::= <int> | <identifier> | <unop> <factor> | "(" <type-name> ")" <factor>
+///            | "(" <exp> ")" | <identifier> "(" [ <exp> { "," <exp> } ] ")"
+/// <unop> ::= "-" | "~" | "!"
+/// <type-name> ::= { <type-specifier> }+ { "*" }
+/// <type-specifier> ::= "int" | "long" | "char"
+/// ```
+///
+/// Binary operator precedence is given by [`precedence`].
+pub fn parse(tokens: Vec<Token>) -> Result<Program, ParseError> {
+    parse_program(&mut TokenCursor::new(tokens))
+}
+
+/// Parses a token stream into a [`Program`], like [`parse`], locating any error by
+/// the span of the token it was found at.
+///
+/// Running out of tokens inside parentheses or braces is reported as
+/// [`ParseError::UnclosedDelimiter`], located at the innermost one left open.
+pub fn parse_with_spans(tokens: Vec<(Token, Span)>) -> Result<Program, SpannedParseError> {
+    let mut tokens = TokenCursor::with_spans(tokens);
+    parse_program(&mut tokens).map_err(|error| {
+        let span = match error {
+            ParseError::UnclosedDelimiter { open_at } => Some(open_at),
+            _ => tokens.span(tokens.consumed().saturating_sub(1)),
+        };
+        SpannedParseError { error, span }
+    })
+}
+
+fn parse_program(tokens: &mut TokenCursor) -> Result<Program, ParseError> {
+    let mut declarations = Vec::new();
+    while tokens.peek().is_some() {
+        declarations.extend(parse_file_scope_item(tokens)?);
+    }
+
+    Ok(Program { declarations })
+}
+
+/// Parses a declaration at file scope, or a `_Static_if` block of them, into the
+/// declarations it contributes to the program.
+fn parse_file_scope_item(tokens: &mut TokenCursor) -> Result<Vec<Declaration>, ParseError> {
+    if tokens.next_if_eq(&Token::StaticIfKeyword).is_some() {
+        return parse_static_if(tokens, parse_file_scope_block);
+    }
+
+    // Only functions may be declared at file scope
+    tokens.expect(Token::IntKeyword)?;
+    let name = parse_identifier(tokens)?;
+    let function = parse_function_declaration(tokens, name)?;
+    Ok(vec![Declaration::Function(Box::new(function))])
+}
+
+/// Parses a brace-delimited block of file scope items, as a `_Static_if` at file
+/// scope has, into their declarations.
+fn parse_file_scope_block(tokens: &mut TokenCursor) -> Result<Vec<Declaration>, ParseError> {
+    tokens.expect(Token::OpenBrace)?;
+    let mut declarations = Vec::new();
+    while !matches!(tokens.peek(), Some(Token::CloseBrace) | None) {
+        declarations.extend(parse_file_scope_item(tokens)?);
+    }
+    expect_closing(tokens, Token::CloseBrace, "`_Static_if` block")?;
+
+    Ok(declarations)
+}
+
+/// Parses the remainder of a function declaration, after its name.
+fn parse_function_declaration(
+    tokens: &mut TokenCursor,
+    name: String,
+) -> Result<FunctionDefinition, ParseError> {
+    // The name was the last token consumed
+    let span = tokens.span(tokens.consumed() - 1);
+    let (params, has_prototype) = parse_params(tokens)?;
+    let body = if tokens.next_if_eq(&Token::Semicolon).is_some() {
+        None
+    } else {
+        if params.iter().any(|(_, name)| name.is_none()) {
+            return Err(ParseError::UnnamedParameter { function: name });
+        }
+        Some(Statement::Compound(parse_block(tokens)?))
+    };
+
+    Ok(FunctionDefinition {
+        name,
+        params,
+        has_prototype,
+        body,
+        span,
+    })
+}
+
+/// Parameter types and names, as in [`FunctionDefinition::params`]
+type Params = Vec<(Type, Option<String>)>;
+
+/// Parses a parenthesized parameter list, and whether it is a prototype (i.e. not `()`).
+fn parse_params(tokens: &mut TokenCursor) -> Result<(Params, bool), ParseError> {
+    tokens.expect(Token::OpenParenthesis)?;
+
+    let mut params = Vec::new();
+    let has_prototype = tokens.peek() != Some(&Token::CloseParenthesis);
+    if has_prototype && tokens.next_if_eq(&Token::VoidKeyword).is_none() {
+        loop {
+            let ty = parse_type_name(tokens)?;
+            let name = match tokens.next_if(|token| matches!(token, Token::Identifier(_))) {
+                Some(Token::Identifier(name)) => Some(name),
+                _ => None,
+            };
+            params.push((ty, name));
+
+            if tokens.next_if_eq(&Token::Comma).is_none() {
+                break;
+            }
+        }
+    }
+    expect_closing(tokens, Token::CloseParenthesis, "parameter list")?;
+
+    Ok((params, has_prototype))
+}
+
+/// Parses a brace-delimited block into its items.
+fn parse_block(tokens: &mut TokenCursor) -> Result<Vec<BlockItem>, ParseError> {
+    tokens.expect(Token::OpenBrace)?;
+    let mut items = Vec::new();
+    loop {
+        match tokens.peek() {
+            Some(Token::CloseBrace) | None => break,
+            Some(Token::StaticIfKeyword) => {
+                tokens.advance();
+                items.extend(parse_static_if(tokens, parse_block)?);
+            }
+            Some(_) => items.push(parse_block_item(tokens)?),
+        }
+    }
+    expect_closing(tokens, Token::CloseBrace, "block")?;
+
+    Ok(items)
+}
+
+fn parse_block_item(tokens: &mut TokenCursor) -> Result<BlockItem, ParseError> {
+    if tokens.peek() == Some(&Token::IntKeyword) {
+        Ok(BlockItem::Declaration(parse_declaration(tokens)?))
+    } else {
+        Ok(BlockItem::Statement(parse_statement(tokens)?))
+    }
+}
+
+fn parse_declaration(tokens: &mut TokenCursor) -> Result<Declaration, ParseError> {
+    tokens.expect(Token::IntKeyword)?;
+    let name = parse_identifier(tokens)?;
+    if tokens.peek() == Some(&Token::OpenParenthesis) {
+        Ok(Declaration::Function(Box::new(parse_function_declaration(
+            tokens, name,
+        )?)))
+    } else {
+        Ok(Declaration::Variable(parse_variable_declaration(
+            tokens, name,
+        )?))
+    }
+}
+
+/// Parses the remainder of a variable declaration, after its name.
+fn parse_variable_declaration(
+    tokens: &mut TokenCursor,
+    name: String,
+) -> Result<VariableDeclaration, ParseError> {
+    let init = if tokens.next_if_eq(&Token::Equal).is_some() {
+        Some(parse_expression(tokens, ASSIGNMENT_PREC)?)
+    } else {
+        None
+    };
+    tokens.expect(Token::Semicolon)?;
+
+    Ok(VariableDeclaration { name, init })
+}
+
+/// Parses the remainder of a `_Static_if (<exp>) { ... }` block, after the keyword,
+/// using `parse_body` to parse the block.
+///
+/// `_Static_if` is a non-standard extension for selecting code at compile time. The
+/// condition must be an integer constant expression. If it is nonzero, the items in the
+/// block are returned to be spliced into the enclosing block or file scope (like `#if`,
+/// the block does not introduce a scope). Otherwise, the items are parsed but
+/// discarded, so they never reach semantic analysis.
+fn parse_static_if<T>(
+    tokens: &mut TokenCursor,
+    parse_body: fn(&mut TokenCursor) -> Result<Vec<T>, ParseError>,
+) -> Result<Vec<T>, ParseError> {
+    tokens.expect(Token::OpenParenthesis)?;
+    let condition = parse_expression(tokens, 0)?;
+    expect_closing(tokens, Token::CloseParenthesis, "`_Static_if` condition")?;
+    let condition = const_eval(&condition).ok_or(ParseError::NotConstant)?;
+    let items = parse_body(tokens)?;
+
+    Ok(if condition != 0 { items } else { Vec::new() })
+}
+
+fn parse_statement(tokens: &mut TokenCursor) -> Result<Statement, ParseError> {
+    let statement = match tokens.peek() {
+        Some(Token::ReturnKeyword) => {
+            tokens.advance();
+            Statement::Return(parse_expression(tokens, 0)?)
+        }
+        Some(Token::IfKeyword) => {
+            tokens.advance();
+            tokens.expect(Token::OpenParenthesis)?;
+            let condition = parse_expression(tokens, 0)?;
+            expect_closing(tokens, Token::CloseParenthesis, "`if` condition")?;
+            let then = Box::new(parse_statement(tokens)?);
+            // Greedily taking the `else` here binds it to the nearest `if`, which resolves
+            // the dangling else ambiguity the way C requires
+            let otherwise = if tokens.next_if_eq(&Token::ElseKeyword).is_some() {
+                Some(Box::new(parse_statement(tokens)?))
+            } else {
+                None
+            };
+            // Statements that end in a nested statement or block are not terminated by a
+            // semicolon
+            return Ok(Statement::If {
+                condition,
+                then,
+                otherwise,
+            });
+        }
+        Some(Token::OpenBrace) => return Ok(Statement::Compound(parse_block(tokens)?)),
+        Some(Token::BreakKeyword) => {
+            tokens.advance();
+            Statement::Break { label: None }
+        }
+        Some(Token::ContinueKeyword) => {
+            tokens.advance();
+            Statement::Continue { label: None }
+        }
+        Some(Token::WhileKeyword) => {
+            tokens.advance();
+            tokens.expect(Token::OpenParenthesis)?;
+            let condition = parse_expression(tokens, 0)?;
+            expect_closing(tokens, Token::CloseParenthesis, "`while` condition")?;
+            let body = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::While {
+                condition,
+                body,
+                label: None,
+            });
+        }
+        Some(Token::DoKeyword) => {
+            tokens.advance();
+            let body = Box::new(parse_statement(tokens)?);
+            tokens.expect(Token::WhileKeyword)?;
+            tokens.expect(Token::OpenParenthesis)?;
+            let condition = parse_expression(tokens, 0)?;
+            expect_closing(tokens, Token::CloseParenthesis, "`while` condition")?;
+            Statement::DoWhile {
+                body,
+                condition,
+                label: None,
+            }
+        }
+        Some(Token::ForKeyword) => {
+            tokens.advance();
+            tokens.expect(Token::OpenParenthesis)?;
+            let init = if tokens.next_if_eq(&Token::IntKeyword).is_some() {
+                let name = parse_identifier(tokens)?;
+                ForInit::Declaration(parse_variable_declaration(tokens, name)?)
+            } else {
+                let init = parse_optional_expression(tokens, &Token::Semicolon)?;
+                tokens.expect(Token::Semicolon)?;
+                ForInit::Expression(init)
+            };
+            let condition = parse_optional_expression(tokens, &Token::Semicolon)?;
+            tokens.expect(Token::Semicolon)?;
+            let post = parse_optional_expression(tokens, &Token::CloseParenthesis)?;
+            expect_closing(tokens, Token::CloseParenthesis, "`for` header")?;
+            let body = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::For {
+                init,
+                condition,
+                post,
+                body,
+                label: None,
+            });
+        }
+        Some(Token::SwitchKeyword) => {
+            tokens.advance();
+            tokens.expect(Token::OpenParenthesis)?;
+            let control = parse_expression(tokens, 0)?;
+            expect_closing(tokens, Token::CloseParenthesis, "`switch` expression")?;
+            let body = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::Switch {
+                control,
+                body,
+                label: None,
+                cases: Vec::new(),
+            });
+        }
+        Some(Token::CaseKeyword) => {
+            tokens.advance();
+            let value = parse_expression(tokens, 0)?;
+            let value = const_eval(&value).ok_or(ParseError::NotConstant)?;
+            // Control expressions are always `int`, and case values are converted to
+            // the type of the control expression, wrapping like gcc
+            let value = i64::from(value as i32);
+            tokens.expect(Token::Colon)?;
+            let stmt = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::Case {
+                value,
+                stmt,
+                label: None,
+            });
+        }
+        Some(Token::DefaultKeyword) => {
+            tokens.advance();
+            tokens.expect(Token::Colon)?;
+            let stmt = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::Default { stmt, label: None });
+        }
+        Some(Token::GotoKeyword) => {
+            tokens.advance();
+            Statement::Goto(parse_identifier(tokens)?)
+        }
+        // An identifier followed by a colon can only be a label, as the conditional
+        // operator's colon always follows a `?`
+        Some(Token::Identifier(_)) if tokens.peek_n(1) == Some(&Token::Colon) => {
+            let name = parse_identifier(tokens)?;
+            tokens.advance();
+            let stmt = Box::new(parse_statement(tokens)?);
+            return Ok(Statement::Labeled { name, stmt });
+        }
+        Some(Token::Semicolon) => Statement::Null,
+        _ => Statement::Expression(parse_expression(tokens, 0)?),
+    };
+    tokens.expect(Token::Semicolon)?;
+
+    Ok(statement)
+}
+
+/// Parses an expression, unless the next token is `terminator`, in which case the
+/// expression has been omitted. The terminator is not consumed.
+fn parse_optional_expression(
+    tokens: &mut TokenCursor,
+    terminator: &Token,
+) -> Result<Option<Expression>, ParseError> {
+    if tokens.peek() == Some(terminator) {
+        Ok(None)
+    } else {
+        parse_expression(tokens, 0).map(Some)
+    }
+}
+
+/// Parses an expression using precedence climbing, only consuming binary operators
+/// whose precedence is at least `min_prec`.
+fn parse_expression(tokens: &mut TokenCursor, min_prec: u8) -> Result<Expression, ParseError> {
+    let mut left = parse_factor(tokens)?;
+
+    while let Some(prec) = tokens.peek().and_then(precedence)
+        && prec >= min_prec
+    {
+        // Unwrap is safe, as we peeked the token above
+        left = match tokens.advance().unwrap() {
+            // Assignment and the conditional operator are right-associative, so the right
+            // hand side is parsed with the same minimum precedence rather than one higher
+            Token::Equal => {
+                let value = parse_expression(tokens, prec)?;
+                Expression::Assignment {
+                    target: Box::new(left),
+                    value: Box::new(value),
+                }
+            }
+            Token::QuestionMark => {
+                // The middle operand is delimited by `?` and `:`, so it behaves as if it were
+                // parenthesized
+                let then = parse_expression(tokens, 0)?;
+                tokens.expect(Token::Colon)?;
+                let otherwise = parse_expression(tokens, prec)?;
+                Expression::Conditional {
+                    cond: Box::new(left),
+                    then: Box::new(then),
+                    otherwise: Box::new(otherwise),
+                }
+            }
+            token => {
+                // Unwrap is safe, as every other token with a precedence is a binary operator
+                let op = binary_op(&token).unwrap();
+                let right = parse_expression(tokens, prec + 1)?;
+                Expression::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }
+            }
+        };
+    }
+
+    Ok(left)
+}
+
+fn parse_factor(tokens: &mut TokenCursor) -> Result<Expression, ParseError> {
+    match tokens.advance_or_eof()? {
+        Token::Constant(c) => {
+            // Constants are only ever digits, so this can only fail by overflowing
+            let value = c
+                .parse()
+                .map_err(|_| ParseError::IntegerTooLarge { value: c })?;
+            Ok(Expression::Constant(value))
+        }
+        Token::Identifier(name) => {
+            if tokens.next_if_eq(&Token::OpenParenthesis).is_none() {
+                return Ok(Expression::Var(name));
+            }
+            if name == BUILTIN_EXPECT {
+                let expr = parse_expression(tokens, ASSIGNMENT_PREC)?;
+                tokens.expect(Token::Comma)?;
+                let expected = parse_expression(tokens, ASSIGNMENT_PREC)?;
+                expect_closing(tokens, Token::CloseParenthesis, "argument list")?;
+                return Ok(Expression::Expect {
+                    expr: Box::new(expr),
+                    expected: Box::new(expected),
+                });
+            }
+
+            let mut args = Vec::new();
+            if tokens.peek() != Some(&Token::CloseParenthesis) {
+                loop {
+                    // Commas separate the arguments, so a comma expression must be
+                    // parenthesized to be passed as one
+                    args.push(parse_expression(tokens, ASSIGNMENT_PREC)?);
+                    if tokens.next_if_eq(&Token::Comma).is_none() {
+                        break;
+                    }
+                }
+            }
+            expect_closing(tokens, Token::CloseParenthesis, "argument list")?;
+            Ok(Expression::FunctionCall { name, args })
+        }
+        // Prefix operators are only ever seen here, at the start of a factor, whereas the
+        // binary operator loop in `parse_expression` only sees infix operators. That is
+        // what distinguishes negation from subtraction.
+        token @ (Token::Hyphen | Token::Tilde | Token::Exclamation) => {
+            let op = match token {
+                Token::Hyphen => UnaryOp::Negate,
+                Token::Tilde => UnaryOp::Complement,
+                _ => UnaryOp::Not,
+            };
+            let expr = parse_factor(tokens)?;
+            Ok(Expression::Unary {
+                op,
+                expr: Box::new(expr),
+            })
+        }
+        Token::OpenParenthesis => {
+            // A parenthesized type name makes this a cast rather than a parenthesized
+            // expression
+            if tokens.peek().is_some_and(is_type_specifier) {
+                let ty = parse_type_name(tokens)?;
+                expect_closing(tokens, Token::CloseParenthesis, "cast")?;
+                let expr = parse_factor(tokens)?;
+                Ok(Expression::Cast {
+                    ty,
+                    expr: Box::new(expr),
+                })
+            } else {
+                let exp = parse_expression(tokens, 0)?;
+                expect_closing(tokens, Token::CloseParenthesis, "parenthesized expression")?;
+                Ok(exp)
+            }
+        }
+        token => Err(ParseError::UnexpectedToken {
+            expected: "expression".to_string(),
+            found: Some(token),
+        }),
+    }
+}
+
+fn is_type_specifier(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::IntKeyword | Token::LongKeyword | Token::CharKeyword
+    )
+}
+
+/// Parses a type name, as used in casts: one or more type specifiers followed by
+/// any number of `*`s.
+fn parse_type_name(tokens: &mut TokenCursor) -> Result<Type, ParseError> {
+    let mut specifiers = Vec::new();
+    while let Some(token) = tokens.next_if(is_type_specifier) {
+        specifiers.push(token);
+    }
+    if specifiers.is_empty() {
+        return Err(ParseError::UnexpectedToken {
+            expected: "type specifier".to_string(),
+            found: tokens.advance(),
+        });
+    }
+
+    let mut ty = match specifiers.as_slice() {
+        [Token::IntKeyword] => Type::Int,
+        [Token::LongKeyword]
+        | [Token::LongKeyword, Token::IntKeyword]
+        | [Token::IntKeyword, Token::LongKeyword] => Type::Long,
+        [Token::CharKeyword] => Type::Char,
+        _ => {
+            return Err(ParseError::UnexpectedToken {
+                expected: "valid type specifier".to_string(),
+                found: specifiers.pop(),
+            });
+        }
+    };
+
+    while tokens.next_if_eq(&Token::Asterisk).is_some() {
+        ty = Type::Pointer(Box::new(ty));
+    }
+
+    Ok(ty)
+}
+
+/// Minimum precedence of an assignment expression, which is any expression other
+/// than an unparenthesized comma expression. Used where commas separate expressions.
+const ASSIGNMENT_PREC: u8 = 1;
+
+/// Name of the builtin that is parsed as [`Expression::Expect`], rather than as a call
+const BUILTIN_EXPECT: &str = "__builtin_expect";
+
+/// Returns the precedence of `token` when it is used as a binary operator, or `None`
+/// if it is not a binary operator. Higher values bind more tightly.
+fn precedence(token: &Token) -> Option<u8> {
+    let prec = match token {
+        Token::Asterisk | Token::ForwardSlash | Token::Percent => 50,
+        Token::Plus | Token::Hyphen => 45,
+        Token::DoubleLessThan | Token::DoubleGreaterThan => 40,
+        Token::LessThan | Token::LessThanEqual | Token::GreaterThan | Token::GreaterThanEqual => 35,
+        Token::DoubleEqual | Token::ExclamationEqual => 30,
+        Token::Ampersand => 25,
+        Token::Caret => 20,
+        Token::Pipe => 15,
+        Token::DoubleAmpersand => 10,
+        Token::DoublePipe => 5,
+        Token::QuestionMark => 3,
+        Token::Equal => ASSIGNMENT_PREC,
+        Token::Comma => 0,
+        _ => return None,
+    };
+    Some(prec)
+}
+
+/// Maps a binary operator token to its [`BinaryOp`].
+fn binary_op(token: &Token) -> Option<BinaryOp> {
+    let op = match token {
+        Token::Asterisk => BinaryOp::Multiply,
+        Token::ForwardSlash => BinaryOp::Divide,
+        Token::Percent => BinaryOp::Remainder,
+        Token::Plus => BinaryOp::Add,
+        Token::Hyphen => BinaryOp::Subtract,
+        Token::DoubleLessThan => BinaryOp::LeftShift,
+        Token::DoubleGreaterThan => BinaryOp::RightShift,
+        Token::LessThan => BinaryOp::LessThan,
+        Token::LessThanEqual => BinaryOp::LessOrEqual,
+        Token::GreaterThan => BinaryOp::GreaterThan,
+        Token::GreaterThanEqual => BinaryOp::GreaterOrEqual,
+        Token::DoubleEqual => BinaryOp::Equal,
+        Token::ExclamationEqual => BinaryOp::NotEqual,
+        Token::Ampersand => BinaryOp::BitwiseAnd,
+        Token::Caret => BinaryOp::BitwiseXor,
+        Token::Pipe => BinaryOp::BitwiseOr,
+        Token::DoubleAmpersand => BinaryOp::And,
+        Token::DoublePipe => BinaryOp::Or,
+        Token::Comma => BinaryOp::Comma,
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn parse_identifier(tokens: &mut TokenCursor) -> Result<String, ParseError> {
+    match tokens.advance_or_eof()? {
+        Token::Identifier(name) => Ok(name),
+        token => Err(ParseError::UnexpectedToken {
+            expected: "identifier".to_string(),
+            found: Some(token),
+        }),
+    }
+}
+
+/// Consumes the `closing` delimiter of a `construct`, erroring with a message naming
+/// the construct if it is missing.
+fn expect_closing(
+    tokens: &mut TokenCursor,
+    closing: Token,
+    construct: &str,
+) -> Result<(), ParseError> {
+    let found = tokens.advance();
+    if found.as_ref() == Some(&closing) {
+        return Ok(());
+    }
+    if found.is_none()
+        && let Some(error) = tokens.unclosed_delimiter()
+    {
+        return Err(error);
+    }
+    Err(ParseError::UnexpectedToken {
+        expected: format!("{closing} to close {construct}"),
+        found,
+    })
+}
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
+
+    fn parse_str(input: &str) -> Result<Program, ParseError> {
+        parse(lexer::tokenize_str(input).unwrap())
+    }
+
+    #[test]
+    fn test_parse_return_constant() {
+        assert_eq!(
+            parse_str("int main(void) { return 2; }"),
+            Ok(Program {
+                declarations: vec![Declaration::Function(Box::new(FunctionDefinition {
+                    name: "main".to_string(),
+                    params: vec![],
+                    has_prototype: true,
+                    body: Some(Statement::Compound(vec![BlockItem::Statement(
+                        Statement::Return(Expression::Constant(2))
+                    )])),
+                    span: None,
+                }))]
+            })
+        );
+    }
+
+    /// Parses `input` as a program, returning the items in the body of its only function.
+    fn parse_body_str(input: &str) -> Vec<BlockItem> {
+        match parse_str(input).unwrap().declarations.as_slice() {
+            [Declaration::Function(function)] => match &function.body {
+                Some(Statement::Compound(items)) => items.clone(),
+                body => panic!("expected a function definition: {body:?}"),
+            },
+            declarations => panic!("expected a single function definition: {declarations:?}"),
+        }
+    }
+
+    fn declaration(name: &str, init: Option<Expression>) -> BlockItem {
+        BlockItem::Declaration(Declaration::Variable(VariableDeclaration {
+            name: name.to_string(),
+            init,
+        }))
+    }
+
+    #[test]
+    fn test_parse_declarations_and_assignment() {
+        let body = parse_body_str(
+            "int main(void) {
+                int x = 5;
+                int y;
+                x = y + 1;
+                ;
+                return x;
+            }",
+        );
+        assert_eq!(
+            body,
+            vec![
+                declaration("x", Some(Expression::Constant(5))),
+                declaration("y", None),
+                BlockItem::Statement(Statement::Expression(Expression::Assignment {
+                    target: Box::new(Expression::Var("x".to_string())),
+                    value: Box::new(Expression::Binary {
+                        op: BinaryOp::Add,
+                        left: Box::new(Expression::Var("y".to_string())),
+                        right: Box::new(Expression::Constant(1)),
+                    }),
+                })),
+                BlockItem::Statement(Statement::Null),
+                BlockItem::Statement(Statement::Return(Expression::Var("x".to_string()))),
+            ]
+        );
+
+        // Whether the target of an assignment is an lvalue is left to semantic analysis
+        assert_eq!(
+            parse_body_str("int main(void) { 2 = 3; }"),
+            vec![BlockItem::Statement(Statement::Expression(
+                Expression::Assignment {
+                    target: Box::new(Expression::Constant(2)),
+                    value: Box::new(Expression::Constant(3)),
+                }
+            ))]
+        );
+        assert_eq!(
+            parse_str("int main(void) { int 3; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "identifier".to_string(),
+                found: Some(Token::Constant("3".to_string()))
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void) { int x = ; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+    }
+
+    fn parse_statement_str(input: &str) -> Result<Statement, ParseError> {
+        let mut tokens = TokenCursor::new(lexer::tokenize_str(input).unwrap());
+        let statement = parse_statement(&mut tokens)?;
+        assert_eq!(tokens.advance(), None);
+        Ok(statement)
+    }
+
+    fn return_statement(value: i64) -> Box<Statement> {
+        Box::new(Statement::Return(Expression::Constant(value)))
+    }
+
+    #[test]
+    fn test_parse_if() {
+        assert_eq!(
+            parse_statement_str("if (a) return 1;"),
+            Ok(Statement::If {
+                condition: Expression::Var("a".to_string()),
+                then: return_statement(1),
+                otherwise: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("if (a == 1) return 1; else return 2;"),
+            Ok(Statement::If {
+                condition: *binary(BinaryOp::Equal, var("a"), constant(1)),
+                then: return_statement(1),
+                otherwise: Some(return_statement(2)),
+            })
+        );
+        assert_eq!(
+            parse_statement_str("if (a) return 1"),
+            Err(ParseError::UnexpectedEof)
+        );
+        assert_eq!(
+            parse_statement_str("if a return 1;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`(`".to_string(),
+                found: Some(Token::Identifier("a".to_string()))
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_dangling_else() {
+        // The `else` belongs to the innermost `if`
+        assert_eq!(
+            parse_statement_str("if (a) if (b) return 1; else return 2;"),
+            Ok(Statement::If {
+                condition: Expression::Var("a".to_string()),
+                then: Box::new(Statement::If {
+                    condition: Expression::Var("b".to_string()),
+                    then: return_statement(1),
+                    otherwise: Some(return_statement(2)),
+                }),
+                otherwise: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("if (a) if (b) return 1; else return 2; else return 3;"),
+            Ok(Statement::If {
+                condition: Expression::Var("a".to_string()),
+                then: Box::new(Statement::If {
+                    condition: Expression::Var("b".to_string()),
+                    then: return_statement(1),
+                    otherwise: Some(return_statement(2)),
+                }),
+                otherwise: Some(return_statement(3)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_static_if() {
+        let body = parse_body_str;
+
+        assert_eq!(
+            body("int main(void) { _Static_if (1) { int x = 1; return x; } return 0; }"),
+            vec![
+                declaration("x", Some(Expression::Constant(1))),
+                BlockItem::Statement(Statement::Return(Expression::Var("x".to_string()))),
+                BlockItem::Statement(*return_statement(0)),
+            ]
+        );
+        // The block is dropped entirely, so the undeclared name never reaches later passes
+        assert_eq!(
+            body("int main(void) { _Static_if (2 - 2) { return undeclared; } return 0; }"),
+            vec![BlockItem::Statement(*return_statement(0))]
+        );
+        assert_eq!(
+            body(
+                "int main(void) {
+                    _Static_if (1) { _Static_if (0) { return 1; } return 2; }
+                }"
+            ),
+            vec![BlockItem::Statement(*return_statement(2))]
+        );
+
+        // At file scope, whole functions are selected
+        let program = parse_str(
+            "_Static_if (0) { int f(void) { return undeclared; } }
+            _Static_if (1) { int g(void); _Static_if (0) { int h(void); } }
+            int main(void) { return 0; }",
+        )
+        .unwrap();
+        let names = program
+            .declarations
+            .iter()
+            .map(|declaration| match declaration {
+                Declaration::Function(function) => function.name.as_str(),
+                Declaration::Variable(variable) => variable.name.as_str(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["g", "main"]);
+        assert_eq!(
+            parse_str("_Static_if (1) { int x = 1; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`(`".to_string(),
+                found: Some(Token::Equal)
+            })
+        );
+
+        let err = parse_str("int main(void) { _Static_if (x) { return 1; } }").unwrap_err();
+        assert_eq!(err, ParseError::NotConstant);
+        assert_eq!(err.to_string(), "expected integer constant expression");
+        assert_eq!(
+            parse_str("int main(void) { _Static_if (1) return 1; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`{`".to_string(),
+                found: Some(Token::ReturnKeyword)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_loops() {
+        assert_eq!(
+            parse_statement_str("while(x) x=x-1;"),
+            Ok(Statement::While {
+                condition: *var("x"),
+                body: Box::new(Statement::Expression(Expression::Assignment {
+                    target: var("x"),
+                    value: binary(BinaryOp::Subtract, var("x"), constant(1)),
+                })),
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("do { } while(c);"),
+            Ok(Statement::DoWhile {
+                body: Box::new(Statement::Compound(vec![])),
+                condition: *var("c"),
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("for(;;) { break; continue; }"),
+            Ok(Statement::For {
+                init: ForInit::Expression(None),
+                condition: None,
+                post: None,
+                body: Box::new(Statement::Compound(vec![
+                    BlockItem::Statement(Statement::Break { label: None }),
+                    BlockItem::Statement(Statement::Continue { label: None }),
+                ])),
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("for (int i = 0; i < 10; i = i + 1) ;"),
+            Ok(Statement::For {
+                init: ForInit::Declaration(VariableDeclaration {
+                    name: "i".to_string(),
+                    init: Some(*constant(0)),
+                }),
+                condition: Some(*binary(BinaryOp::LessThan, var("i"), constant(10))),
+                post: Some(Expression::Assignment {
+                    target: var("i"),
+                    value: binary(BinaryOp::Add, var("i"), constant(1)),
+                }),
+                body: Box::new(Statement::Null),
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("for (i = 0; ; ) break;"),
+            Ok(Statement::For {
+                init: ForInit::Expression(Some(Expression::Assignment {
+                    target: var("i"),
+                    value: constant(0),
+                })),
+                condition: None,
+                post: None,
+                body: Box::new(Statement::Break { label: None }),
+                label: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_loop_errors() {
+        assert_eq!(
+            parse_statement_str("do x = 1; while (x)"),
+            Err(ParseError::UnexpectedEof)
+        );
+        assert_eq!(
+            parse_statement_str("for (;) ;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::CloseParenthesis)
+            })
+        );
+        assert_eq!(
+            parse_statement_str("for (;; ;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+        assert_eq!(parse_statement_str("break"), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_parse_goto() {
+        assert_eq!(
+            parse_statement_str("goto end;"),
+            Ok(Statement::Goto("end".to_string()))
+        );
+        assert_eq!(
+            parse_statement_str("end: return 1;"),
+            Ok(Statement::Labeled {
+                name: "end".to_string(),
+                stmt: return_statement(1),
+            })
+        );
+        // Labels can be nested, and a label's statement can be a null statement
+        assert_eq!(
+            parse_statement_str("a: b: ;"),
+            Ok(Statement::Labeled {
+                name: "a".to_string(),
+                stmt: Box::new(Statement::Labeled {
+                    name: "b".to_string(),
+                    stmt: Box::new(Statement::Null),
+                }),
+            })
+        );
+        // Only an identifier directly followed by a colon is a label
+        assert_eq!(
+            parse_statement_str("a ? b : c;"),
+            Ok(Statement::Expression(Expression::Conditional {
+                cond: Box::new(Expression::Var("a".to_string())),
+                then: Box::new(Expression::Var("b".to_string())),
+                otherwise: Box::new(Expression::Var("c".to_string())),
+            }))
+        );
+        assert_eq!(
+            parse_statement_str("goto 1;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "identifier".to_string(),
+                found: Some(Token::Constant("1".to_string()))
+            })
+        );
+        assert_eq!(parse_statement_str("end:"), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_parse_switch() {
+        assert_eq!(
+            parse_statement_str("switch (x) { case 1 + 1: return 1; default: ; }"),
+            Ok(Statement::Switch {
+                control: Expression::Var("x".to_string()),
+                body: Box::new(Statement::Compound(vec![
+                    BlockItem::Statement(Statement::Case {
+                        value: 2,
+                        stmt: return_statement(1),
+                        label: None,
+                    }),
+                    BlockItem::Statement(Statement::Default {
+                        stmt: Box::new(Statement::Null),
+                        label: None,
+                    }),
+                ])),
+                label: None,
+                cases: vec![],
+            })
+        );
+        // Values are converted to `int`
+        assert_eq!(
+            parse_statement_str("case 4294967297: ;"),
+            Ok(Statement::Case {
+                value: 1,
+                stmt: Box::new(Statement::Null),
+                label: None,
+            })
+        );
+        assert_eq!(
+            parse_statement_str("case x: ;"),
+            Err(ParseError::NotConstant)
+        );
+        assert_eq!(
+            parse_statement_str("default ;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`:`".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+    }
+
+    fn function(
+        name: &str,
+        params: &[(Type, Option<&str>)],
+        body: Option<Vec<BlockItem>>,
+    ) -> Declaration {
+        Declaration::Function(Box::new(FunctionDefinition {
+            name: name.to_string(),
+            params: params
+                .iter()
+                .map(|(ty, name)| (ty.clone(), name.map(str::to_string)))
+                .collect(),
+            has_prototype: true,
+            body: body.map(Statement::Compound),
+            span: None,
+        }))
+    }
+
+    /// Turns a declaration made by [`function`] into one with `()` as its parameters.
+    fn without_prototype(declaration: Declaration) -> Declaration {
+        match declaration {
+            Declaration::Function(function) => {
+                Declaration::Function(Box::new(FunctionDefinition {
+                    has_prototype: false,
+                    ..*function
+                }))
+            }
+            declaration => declaration,
+        }
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_eq!(
+            parse_str(
+                "int add(int a, long b) { return a + b; }
+                int main(void) { return add(1, 2 * 3); }"
+            ),
+            Ok(Program {
+                declarations: vec![
+                    function(
+                        "add",
+                        &[(Type::Int, Some("a")), (Type::Long, Some("b"))],
+                        Some(vec![BlockItem::Statement(Statement::Return(*binary(
+                            BinaryOp::Add,
+                            var("a"),
+                            var("b")
+                        )))])
+                    ),
+                    function(
+                        "main",
+                        &[],
+                        Some(vec![BlockItem::Statement(Statement::Return(
+                            Expression::FunctionCall {
+                                name: "add".to_string(),
+                                args: vec![
+                                    *constant(1),
+                                    *binary(BinaryOp::Multiply, constant(2), constant(3))
+                                ],
+                            }
+                        ))])
+                    ),
+                ]
+            })
+        );
+        assert_eq!(
+            parse_str(""),
+            Ok(Program {
+                declarations: vec![]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_forward_declaration() {
+        assert_eq!(
+            parse_str(
+                "int f(int, int);
+                int main() { int g(void); return f(g(), 1); }
+                int f(int a, int b) { return a; }"
+            ),
+            Ok(Program {
+                declarations: vec![
+                    function("f", &[(Type::Int, None), (Type::Int, None)], None),
+                    without_prototype(function(
+                        "main",
+                        &[],
+                        Some(vec![
+                            BlockItem::Declaration(function("g", &[], None)),
+                            BlockItem::Statement(Statement::Return(Expression::FunctionCall {
+                                name: "f".to_string(),
+                                args: vec![
+                                    Expression::FunctionCall {
+                                        name: "g".to_string(),
+                                        args: vec![],
+                                    },
+                                    *constant(1)
+                                ],
+                            }))
+                        ])
+                    )),
+                    function(
+                        "f",
+                        &[(Type::Int, Some("a")), (Type::Int, Some("b"))],
+                        Some(vec![BlockItem::Statement(Statement::Return(*var("a")))])
+                    ),
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_void_params() {
+        assert_eq!(
+            parse_str("int f(void); int g(); int h(int);"),
+            Ok(Program {
+                declarations: vec![
+                    function("f", &[], None),
+                    without_prototype(function("g", &[], None)),
+                    function("h", &[(Type::Int, None)], None),
+                ]
+            })
+        );
+        assert_eq!(
+            parse_str("int f(void, int);"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)` to close parameter list".to_string(),
+                found: Some(Token::Comma)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_function_errors() {
+        let err = parse_str("int f(int a, int) { return a; }").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::UnnamedParameter {
+                function: "f".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "parameter name omitted in definition of `f`"
+        );
+        assert_eq!(
+            parse_str("int f(a) { return a; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "type specifier".to_string(),
+                found: Some(Token::Identifier("a".to_string()))
+            })
+        );
+        assert_eq!(
+            parse_str("int f(void) { return g(1, ); }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::CloseParenthesis)
+            })
+        );
+        assert_eq!(
+            parse_str("int f(void) { return g(1; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)` to close argument list".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+        // Only functions can be declared at file scope
+        assert_eq!(
+            parse_str("int x = 1;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`(`".to_string(),
+                found: Some(Token::Equal)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        use Token::*;
+
+        assert_eq!(
+            parse_str("int main(void) { return 2; }}"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`int`".to_string(),
+                found: Some(CloseBrace)
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void) { return 2 }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`;`".to_string(),
+                found: Some(CloseBrace)
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void { return 2; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)` to close parameter list".to_string(),
+                found: Some(OpenBrace)
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void) return 2;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`{`".to_string(),
+                found: Some(ReturnKeyword)
+            })
+        );
+        assert_eq!(
+            parse_str("int 3(void) { return 2; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "identifier".to_string(),
+                found: Some(Constant("3".to_string()))
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void) { return 2;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`}` to close block".to_string(),
+                found: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_with_spans() {
+        let parse_spanned_str = |input: &str| {
+            let err = parse_with_spans(lexer::tokenize_str_with_spans(input).unwrap()).unwrap_err();
+            (err.error, err.span.unwrap().line_column(input))
+        };
+        assert_eq!(
+            parse_spanned_str("int main(void) {\n    return -;\n}"),
+            (
+                ParseError::UnexpectedToken {
+                    expected: "expression".to_string(),
+                    found: Some(Token::Semicolon)
+                },
+                (2, 13)
+            )
+        );
+        assert_eq!(
+            parse_spanned_str("int main(void) { return +; }"),
+            (
+                ParseError::UnexpectedToken {
+                    expected: "expression".to_string(),
+                    found: Some(Token::Plus)
+                },
+                (1, 25)
+            )
+        );
+        // At the end of the tokens, the error is at the last one
+        assert_eq!(
+            parse_spanned_str("\n  int"),
+            (ParseError::UnexpectedEof, (2, 3))
+        );
+
+        let input = "int main(void) { return 2 }";
+        let err = parse_with_spans(lexer::tokenize_str_with_spans(input).unwrap()).unwrap_err();
+        assert_eq!(
+            err.into_driver_error(input),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: expected `;`, found `}` at line 1, column 27.".to_string(),
+                span: Some((26, 27)),
+                ..Default::default()
+            }
+        );
+        assert!(parse_with_spans(vec![]).is_ok());
+
+        // Functions record where their name is
+        let input = "int f(void);\nint main(void) { return 0; }";
+        let program = parse_with_spans(lexer::tokenize_str_with_spans(input).unwrap()).unwrap();
+        let spans = program
+            .declarations
+            .iter()
+            .map(|declaration| match declaration {
+                Declaration::Function(function) => function.span,
+                Declaration::Variable(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            [
+                Some(Span { start: 4, end: 5 }),
+                Some(Span { start: 17, end: 21 })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_unclosed_delimiter() {
+        let parse_spanned_str = |input: &str| {
+            let err = parse_with_spans(lexer::tokenize_str_with_spans(input).unwrap()).unwrap_err();
+            (err.error, err.span.unwrap().line_column(input))
+        };
+        // Running out of tokens points at the brace left open, not the end
+        assert_eq!(
+            parse_spanned_str("int main(void) { return 2;"),
+            (
+                ParseError::UnclosedDelimiter {
+                    open_at: Span { start: 15, end: 16 }
+                },
+                (1, 16)
+            )
+        );
+        assert_eq!(
+            parse_spanned_str("int main(void) {\n    if (1) {\n        return (2 +"),
+            (
+                ParseError::UnclosedDelimiter {
+                    open_at: Span { start: 45, end: 46 }
+                },
+                (3, 16)
+            )
+        );
+        // Delimiters that were closed don't count
+        assert_eq!(
+            parse_spanned_str("int main(void) { return f(1) }"),
+            (
+                ParseError::UnexpectedToken {
+                    expected: "`;`".to_string(),
+                    found: Some(Token::CloseBrace)
+                },
+                (1, 30)
+            )
+        );
+
+        let input = "int main(void) {\n    return 2;";
+        let err = parse_with_spans(lexer::tokenize_str_with_spans(input).unwrap()).unwrap_err();
+        assert_eq!(
+            err.into_driver_error(input),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: unclosed delimiter at line 1, column 16.".to_string(),
+                span: Some((15, 16)),
+                ..Default::default()
+            }
+        );
+
+        // Without spans, this is just the end of the input
+        assert_eq!(
+            parse_str("int main(void) { return 2;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`}` to close block".to_string(),
+                found: None
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_compound() {
+        assert_eq!(
+            parse_body_str("int main(void) { int x = 1; { x = 2; } {} }"),
+            vec![
+                declaration("x", Some(Expression::Constant(1))),
+                BlockItem::Statement(Statement::Compound(vec![BlockItem::Statement(
+                    Statement::Expression(Expression::Assignment {
+                        target: var("x"),
+                        value: constant(2),
+                    })
+                )])),
+                BlockItem::Statement(Statement::Compound(vec![])),
+            ]
+        );
+        assert_eq!(
+            parse_statement_str("if (a) { int b = a; return b; } else {}"),
+            Ok(Statement::If {
+                condition: *var("a"),
+                then: Box::new(Statement::Compound(vec![
+                    declaration("b", Some(*var("a"))),
+                    BlockItem::Statement(Statement::Return(*var("b"))),
+                ])),
+                otherwise: Some(Box::new(Statement::Compound(vec![]))),
+            })
+        );
+
+        let err = parse_str("int main(void) { int x = 1; { x = 2; }").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::UnexpectedToken {
+                expected: "`}` to close block".to_string(),
+                found: None
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "expected `}` to close block, found end of file"
+        );
+        // Declarations are block items, not statements, so cannot be the body of an `if`
+        assert_eq!(
+            parse_statement_str("if (a) int b;"),
+            Err(ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::IntKeyword)
+            })
+        );
+    }
+
+    #[test]
+    fn test_missing_return_value() {
+        let err = parse_str("int main(void) { return }").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::UnexpectedToken {
+                expected: "expression".to_string(),
+                found: Some(Token::CloseBrace)
+            }
+        );
+        assert_eq!(err.to_string(), "expected expression, found `}`");
+        assert_eq!(
+            DriverError::from(err),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed to parse: expected expression, found `}`.".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    fn parse_expression_str(input: &str) -> Result<Expression, ParseError> {
+        let mut tokens = TokenCursor::new(lexer::tokenize_str(input).unwrap());
+        let exp = parse_expression(&mut tokens, 0)?;
+        assert_eq!(tokens.advance(), None);
+        Ok(exp)
+    }
+
+    fn constant(value: i64) -> Box<Expression> {
+        Box::new(Expression::Constant(value))
+    }
+
+    fn var(name: &str) -> Box<Expression> {
+        Box::new(Expression::Var(name.to_string()))
+    }
+
+    fn binary(op: BinaryOp, left: Box<Expression>, right: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Binary { op, left, right })
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        use BinaryOp::*;
+
+        assert_eq!(
+            parse_expression_str("1+2*3"),
+            Ok(*binary(
+                Add,
+                constant(1),
+                binary(Multiply, constant(2), constant(3))
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("1*2+3"),
+            Ok(*binary(
+                Add,
+                binary(Multiply, constant(1), constant(2)),
+                constant(3)
+            ))
+        );
+        // Binary operators are left-associative
+        assert_eq!(
+            parse_expression_str("1-2-3"),
+            Ok(*binary(
+                Subtract,
+                binary(Subtract, constant(1), constant(2)),
+                constant(3)
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("a || b && c == d < e << f + g % h"),
+            Ok(*binary(
+                Or,
+                var("a"),
+                binary(
+                    And,
+                    var("b"),
+                    binary(
+                        Equal,
+                        var("c"),
+                        binary(
+                            LessThan,
+                            var("d"),
+                            binary(
+                                LeftShift,
+                                var("e"),
+                                binary(Add, var("f"), binary(Remainder, var("g"), var("h")))
+                            )
+                        )
+                    )
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("a | b ^ c & d"),
+            Ok(*binary(
+                BitwiseOr,
+                var("a"),
+                binary(BitwiseXor, var("b"), binary(BitwiseAnd, var("c"), var("d")))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        // Assignment is right-associative
+        assert_eq!(
+            parse_expression_str("a=b=c"),
+            Ok(Expression::Assignment {
+                target: var("a"),
+                value: Box::new(Expression::Assignment {
+                    target: var("b"),
+                    value: var("c"),
+                }),
+            })
+        );
+        assert_eq!(
+            parse_expression_str("a = 1 + 2"),
+            Ok(Expression::Assignment {
+                target: var("a"),
+                value: binary(BinaryOp::Add, constant(1), constant(2)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        assert_eq!(
+            parse_expression_str("a ? b : c ? d : e"),
+            Ok(Expression::Conditional {
+                cond: var("a"),
+                then: var("b"),
+                otherwise: Box::new(Expression::Conditional {
+                    cond: var("c"),
+                    then: var("d"),
+                    otherwise: var("e"),
+                }),
+            })
+        );
+        assert_eq!(
+            parse_expression_str("a = b ? c = d : e"),
+            Ok(Expression::Assignment {
+                target: var("a"),
+                value: Box::new(Expression::Conditional {
+                    cond: var("b"),
+                    then: Box::new(Expression::Assignment {
+                        target: var("c"),
+                        value: var("d"),
+                    }),
+                    otherwise: var("e"),
+                }),
+            })
+        );
+        // Every binary operator binds more tightly, on either side
+        assert_eq!(
+            parse_expression_str("a ? b : c + 1"),
+            Ok(Expression::Conditional {
+                cond: var("a"),
+                then: var("b"),
+                otherwise: binary(BinaryOp::Add, var("c"), constant(1)),
+            })
+        );
+        assert_eq!(
+            parse_expression_str("a || b ? c : d"),
+            Ok(Expression::Conditional {
+                cond: binary(BinaryOp::Or, var("a"), var("b")),
+                then: var("c"),
+                otherwise: var("d"),
+            })
+        );
+        assert_eq!(
+            parse_expression_str("(a ? b : c) + 1"),
+            Ok(*binary(
+                BinaryOp::Add,
+                Box::new(Expression::Conditional {
+                    cond: var("a"),
+                    then: var("b"),
+                    otherwise: var("c"),
+                }),
+                constant(1)
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("a ? b"),
+            Err(ParseError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_parse_comma() {
+        // The comma operator is left-associative and binds more loosely than assignment
+        assert_eq!(
+            parse_expression_str("a = 1, b, c"),
+            Ok(*binary(
+                BinaryOp::Comma,
+                binary(
+                    BinaryOp::Comma,
+                    Box::new(Expression::Assignment {
+                        target: var("a"),
+                        value: constant(1),
+                    }),
+                    var("b")
+                ),
+                var("c")
+            ))
+        );
+        // Commas separate arguments, unless they are parenthesized
+        assert_eq!(
+            parse_expression_str("f((a, b), c)"),
+            Ok(Expression::FunctionCall {
+                name: "f".to_string(),
+                args: vec![*binary(BinaryOp::Comma, var("a"), var("b")), *var("c")],
+            })
+        );
+        assert_eq!(
+            parse_expression_str("f(a, b)"),
+            Ok(Expression::FunctionCall {
+                name: "f".to_string(),
+                args: vec![*var("a"), *var("b")],
+            })
+        );
+        assert_eq!(
+            parse_expression_str("a ? b, c : d"),
+            Ok(Expression::Conditional {
+                cond: var("a"),
+                then: binary(BinaryOp::Comma, var("b"), var("c")),
+                otherwise: var("d"),
+            })
+        );
+        // Nor does an initializer extend past a comma
+        assert_eq!(
+            parse_str("int main(void) { int x = 1, 2; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`;`".to_string(),
+                found: Some(Token::Comma)
+            })
+        );
+        assert_eq!(
+            parse_str("int main(void) { int x = (1, 2); }").map(|_| ()),
+            Ok(())
+        );
+    }
+
+    fn unary(op: UnaryOp, expr: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Unary { op, expr })
+    }
+
+    #[test]
+    fn test_parse_unary() {
+        use UnaryOp::*;
+
+        assert_eq!(
+            parse_expression_str("-~5"),
+            Ok(*unary(Negate, unary(Complement, constant(5))))
+        );
+        assert_eq!(
+            parse_expression_str("~-3"),
+            Ok(*unary(Complement, unary(Negate, constant(3))))
+        );
+        assert_eq!(parse_expression_str("!a"), Ok(*unary(Not, var("a"))));
+        // Unary operators bind more tightly than binary ones
+        assert_eq!(
+            parse_expression_str("-a * b"),
+            Ok(*binary(
+                BinaryOp::Multiply,
+                unary(Negate, var("a")),
+                var("b")
+            ))
+        );
+        // A `-` in infix position is subtraction; in prefix position it is negation
+        assert_eq!(
+            parse_expression_str("1 - -2"),
+            Ok(*binary(
+                BinaryOp::Subtract,
+                constant(1),
+                unary(Negate, constant(2))
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("-(1 - 2)"),
+            Ok(*unary(
+                Negate,
+                binary(BinaryOp::Subtract, constant(1), constant(2))
+            ))
+        );
+        assert_eq!(parse_expression_str("-"), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_parse_parenthesized() {
+        use BinaryOp::*;
+
+        assert_eq!(
+            parse_expression_str("(1+2)*3"),
+            Ok(*binary(
+                Multiply,
+                binary(Add, constant(1), constant(2)),
+                constant(3)
+            ))
+        );
+        assert_eq!(parse_expression_str("((1))"), Ok(Expression::Constant(1)));
+
+        let err = parse_expression_str("(1+2").unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::UnexpectedToken {
+                expected: "`)` to close parenthesized expression".to_string(),
+                found: None
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "expected `)` to close parenthesized expression, found end of file"
+        );
+        assert_eq!(
+            parse_str("int main(void) { return (1 + 2; }"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)` to close parenthesized expression".to_string(),
+                found: Some(Token::Semicolon)
+            })
+        );
+        assert_eq!(
+            parse_expression_str("(long 1"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`)` to close cast".to_string(),
+                found: Some(Token::Constant("1".to_string()))
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_integer_too_large() {
+        assert_eq!(
+            parse_expression_str("9223372036854775807"),
+            Ok(Expression::Constant(i64::MAX))
+        );
+        assert_eq!(
+            parse_expression_str("55555555555555555504"),
+            Err(ParseError::IntegerTooLarge {
+                value: "55555555555555555504".to_string()
+            })
+        );
+        assert_eq!(
+            ParseError::IntegerTooLarge {
+                value: "9223372036854775808".to_string()
+            }
+            .to_string(),
+            "integer constant `9223372036854775808` is too large for any integer type"
+        );
+    }
+
+    fn cast(ty: Type, expr: Box<Expression>) -> Box<Expression> {
+        Box::new(Expression::Cast { ty, expr })
+    }
+
+    #[test]
+    fn test_parse_builtin_expect() {
+        assert_eq!(
+            parse_expression_str("__builtin_expect(x != 0, 1) + 2"),
+            Ok(*binary(
+                BinaryOp::Add,
+                Box::new(Expression::Expect {
+                    expr: binary(BinaryOp::NotEqual, var("x"), constant(0)),
+                    expected: constant(1),
+                }),
+                constant(2)
+            ))
+        );
+        // Always takes exactly two arguments
+        assert_eq!(
+            parse_expression_str("__builtin_expect(x)"),
+            Err(ParseError::UnexpectedToken {
+                expected: "`,`".to_string(),
+                found: Some(Token::CloseParenthesis)
+            })
+        );
+        assert!(parse_expression_str("__builtin_expect(x, 1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_parse_cast() {
+        assert_eq!(
+            parse_expression_str("(long)x"),
+            Ok(*cast(Type::Long, var("x")))
+        );
+        assert_eq!(
+            parse_expression_str("(int)(y + 1)"),
+            Ok(*cast(
+                Type::Int,
+                binary(BinaryOp::Add, var("y"), constant(1))
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("(char*)0"),
+            Ok(*cast(Type::Pointer(Box::new(Type::Char)), constant(0)))
+        );
+        assert_eq!(
+            parse_expression_str("(long int **)0"),
+            Ok(*cast(
+                Type::Pointer(Box::new(Type::Pointer(Box::new(Type::Long)))),
+                constant(0)
+            ))
+        );
+        // A cast binds more tightly than any binary operator
+        assert_eq!(
+            parse_expression_str("(long)x + 1"),
+            Ok(*binary(
+                BinaryOp::Add,
+                cast(Type::Long, var("x")),
+                constant(1)
+            ))
+        );
+        assert_eq!(
+            parse_expression_str("(char long)x"),
+            Err(ParseError::UnexpectedToken {
+                expected: "valid type specifier".to_string(),
+                found: Some(Token::LongKeyword)
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_parenthesized_variable_is_not_cast() {
+        assert_eq!(parse_expression_str("(a)"), Ok(*var("a")));
+        assert_eq!(
+            parse_expression_str("(a) + 1"),
+            Ok(*binary(BinaryOp::Add, var("a"), constant(1)))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_ast_json_round_trip() {
+        let program = parse_str("int main(void) { return 1 + 2; }").unwrap();
+
+        let json = serde_json::to_value(&program).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "Program",
+                "declarations": [{
+                    "type": "Function",
+                    "value": {
+                        "type": "FunctionDefinition",
+                        "name": "main",
+                        "params": [],
+                        "has_prototype": true,
+                        "body": {
+                            "type": "Compound",
+                            "value": [{
+                                "type": "Statement",
+                                "value": {
+                                    "type": "Return",
+                                    "value": {
+                                        "type": "Binary",
+                                        "value": {
+                                            "op": "Add",
+                                            "left": { "type": "Constant", "value": 1 },
+                                            "right": { "type": "Constant", "value": 2 }
+                                        }
+                                    }
+                                }
+                            }]
+                        }
+                    }
+                }]
+            })
+        );
+
+        let round_tripped: Program = serde_json::from_value(json).unwrap();
+        assert_eq!(round_tripped, program);
+    }
 }
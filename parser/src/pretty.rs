@@ -0,0 +1,339 @@
+//! Human-readable rendering of the AST, for debugging the parser.
+
+use crate::ast::{
+    BlockItem, Declaration, Expression, ForInit, FunctionDefinition, Program, Statement,
+    VariableDeclaration,
+};
+
+/// Renders `program` as an indented tree, with one node per line.
+///
+/// Nodes with children are written as `Name(`, followed by the children indented
+/// one level further, followed by `)`. Named fields are prefixed with `name=`.
+pub fn pretty_print(program: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.branch("", "Program", |p| {
+        for declaration in &program.declarations {
+            p.declaration(declaration);
+        }
+    });
+    printer.out
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    const INDENT: &str = "    ";
+
+    /// Writes a node with no children on a single line.
+    fn leaf(&mut self, prefix: &str, text: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str(Self::INDENT);
+        }
+        self.out.push_str(prefix);
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Writes a node whose children are written by `children`.
+    fn branch(&mut self, prefix: &str, name: &str, children: impl FnOnce(&mut Self)) {
+        self.leaf(prefix, &format!("{name}("));
+        self.depth += 1;
+        children(self);
+        self.depth -= 1;
+        self.leaf("", ")");
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Variable(declaration) => self.variable_declaration(declaration),
+            Declaration::Function(function) => self.function(function),
+        }
+    }
+
+    fn variable_declaration(&mut self, declaration: &VariableDeclaration) {
+        self.branch("", "Declare", |p| {
+            p.leaf("name=", &format!("{:?}", declaration.name));
+            p.optional_expression("init=", declaration.init.as_ref());
+        });
+    }
+
+    fn function(&mut self, function: &FunctionDefinition) {
+        self.branch("", "Function", |p| {
+            p.leaf("name=", &format!("{:?}", function.name));
+            let params = function
+                .params
+                .iter()
+                .map(|(ty, name)| match name {
+                    Some(name) => format!("{ty} {name}"),
+                    None => ty.to_string(),
+                })
+                .collect::<Vec<_>>();
+            let params = if params.is_empty() && function.has_prototype {
+                "void".to_string()
+            } else {
+                params.join(", ")
+            };
+            p.leaf("params=", &format!("({params})"));
+            match &function.body {
+                Some(body) => p.statement("body=", body),
+                None => p.leaf("body=", "None"),
+            }
+        });
+    }
+
+    fn block_item(&mut self, item: &BlockItem) {
+        match item {
+            BlockItem::Statement(statement) => self.statement("", statement),
+            BlockItem::Declaration(declaration) => self.declaration(declaration),
+        }
+    }
+
+    fn statement(&mut self, prefix: &str, statement: &Statement) {
+        match statement {
+            Statement::Return(exp) => self.branch(prefix, "Return", |p| p.expression("", exp)),
+            Statement::Expression(exp) => {
+                self.branch(prefix, "Expression", |p| p.expression("", exp))
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => self.branch(prefix, "If", |p| {
+                p.expression("condition=", condition);
+                p.statement("then=", then);
+                if let Some(otherwise) = otherwise {
+                    p.statement("else=", otherwise);
+                }
+            }),
+            Statement::Compound(items) => self.branch(prefix, "Compound", |p| {
+                for item in items {
+                    p.block_item(item);
+                }
+            }),
+            Statement::Break { label } => self.leaf(prefix, &labeled("Break", label.as_deref())),
+            Statement::Continue { label } => {
+                self.leaf(prefix, &labeled("Continue", label.as_deref()))
+            }
+            Statement::While {
+                condition,
+                body,
+                label,
+            } => self.branch(prefix, &labeled("While", label.as_deref()), |p| {
+                p.expression("condition=", condition);
+                p.statement("body=", body);
+            }),
+            Statement::DoWhile {
+                body,
+                condition,
+                label,
+            } => self.branch(prefix, &labeled("DoWhile", label.as_deref()), |p| {
+                p.statement("body=", body);
+                p.expression("condition=", condition);
+            }),
+            Statement::For {
+                init,
+                condition,
+                post,
+                body,
+                label,
+            } => self.branch(prefix, &labeled("For", label.as_deref()), |p| {
+                match init {
+                    ForInit::Declaration(declaration) => {
+                        p.branch("init=", "", |p| p.variable_declaration(declaration))
+                    }
+                    ForInit::Expression(exp) => p.optional_expression("init=", exp.as_ref()),
+                }
+                p.optional_expression("condition=", condition.as_ref());
+                p.optional_expression("post=", post.as_ref());
+                p.statement("body=", body);
+            }),
+            Statement::Switch {
+                control,
+                body,
+                label,
+                ..
+            } => self.branch(prefix, &labeled("Switch", label.as_deref()), |p| {
+                p.expression("control=", control);
+                p.statement("body=", body);
+            }),
+            Statement::Case { value, stmt, label } => {
+                self.branch(prefix, &labeled("Case", label.as_deref()), |p| {
+                    p.leaf("value=", &value.to_string());
+                    p.statement("", stmt);
+                })
+            }
+            Statement::Default { stmt, label } => {
+                self.branch(prefix, &labeled("Default", label.as_deref()), |p| {
+                    p.statement("", stmt)
+                })
+            }
+            Statement::Goto(label) => self.leaf(prefix, &format!("Goto({label:?})")),
+            Statement::Labeled { name, stmt } => self.branch(prefix, "Labeled", |p| {
+                p.leaf("name=", &format!("{name:?}"));
+                p.statement("", stmt);
+            }),
+            Statement::Null => self.leaf(prefix, "Null"),
+        }
+    }
+
+    fn optional_expression(&mut self, prefix: &str, exp: Option<&Expression>) {
+        match exp {
+            Some(exp) => self.expression(prefix, exp),
+            None => self.leaf(prefix, "None"),
+        }
+    }
+
+    fn expression(&mut self, prefix: &str, exp: &Expression) {
+        match exp {
+            Expression::Constant(value) => self.leaf(prefix, &format!("Constant({value})")),
+            Expression::Var(name) => self.leaf(prefix, &format!("Var({name:?})")),
+            Expression::Unary { op, expr } => self.branch(prefix, "Unary", |p| {
+                p.leaf("op=", &format!("{op:?}"));
+                p.expression("", expr);
+            }),
+            Expression::Binary { op, left, right } => self.branch(prefix, "Binary", |p| {
+                p.leaf("op=", &format!("{op:?}"));
+                p.expression("", left);
+                p.expression("", right);
+            }),
+            Expression::Assignment { target, value } => self.branch(prefix, "Assignment", |p| {
+                p.expression("target=", target);
+                p.expression("value=", value);
+            }),
+            Expression::Conditional {
+                cond,
+                then,
+                otherwise,
+            } => self.branch(prefix, "Conditional", |p| {
+                p.expression("condition=", cond);
+                p.expression("then=", then);
+                p.expression("else=", otherwise);
+            }),
+            Expression::Cast { ty, expr } => self.branch(prefix, "Cast", |p| {
+                p.leaf("type=", &ty.to_string());
+                p.expression("", expr);
+            }),
+            Expression::FunctionCall { name, args } => self.branch(prefix, "Call", |p| {
+                p.leaf("name=", &format!("{name:?}"));
+                for arg in args {
+                    p.expression("", arg);
+                }
+            }),
+            Expression::Expect { expr, expected } => self.branch(prefix, "Expect", |p| {
+                p.expression("", expr);
+                p.expression("expected=", expected);
+            }),
+        }
+    }
+}
+
+/// Adds the label of a loop, or of the loop a `break` or `continue` targets, to
+/// `name`, if loops have been labeled.
+fn labeled(name: &str, label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!("{name}[{label}]"),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty_print_str(input: &str) -> String {
+        pretty_print(&crate::parse(lexer::tokenize_str(input).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_pretty_print_return_constant() {
+        assert_eq!(
+            pretty_print_str("int main(void){return 2;}"),
+            concat!(
+                "Program(\n",
+                "    Function(\n",
+                "        name=\"main\"\n",
+                "        params=(void)\n",
+                "        body=Compound(\n",
+                "            Return(\n",
+                "                Constant(2)\n",
+                "            )\n",
+                "        )\n",
+                "    )\n",
+                ")\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_pretty_print_program() {
+        assert_eq!(
+            pretty_print_str(
+                "int f(int a, long);
+                int main() {
+                    int x = -1;
+                    for (;;) if (x < 2) break; else x = f(x, (long)3);
+                    return x ? 1 : 0;
+                }"
+            ),
+            concat!(
+                "Program(\n",
+                "    Function(\n",
+                "        name=\"f\"\n",
+                "        params=(int a, long)\n",
+                "        body=None\n",
+                "    )\n",
+                "    Function(\n",
+                "        name=\"main\"\n",
+                "        params=()\n",
+                "        body=Compound(\n",
+                "            Declare(\n",
+                "                name=\"x\"\n",
+                "                init=Unary(\n",
+                "                    op=Negate\n",
+                "                    Constant(1)\n",
+                "                )\n",
+                "            )\n",
+                "            For(\n",
+                "                init=None\n",
+                "                condition=None\n",
+                "                post=None\n",
+                "                body=If(\n",
+                "                    condition=Binary(\n",
+                "                        op=LessThan\n",
+                "                        Var(\"x\")\n",
+                "                        Constant(2)\n",
+                "                    )\n",
+                "                    then=Break\n",
+                "                    else=Expression(\n",
+                "                        Assignment(\n",
+                "                            target=Var(\"x\")\n",
+                "                            value=Call(\n",
+                "                                name=\"f\"\n",
+                "                                Var(\"x\")\n",
+                "                                Cast(\n",
+                "                                    type=long\n",
+                "                                    Constant(3)\n",
+                "                                )\n",
+                "                            )\n",
+                "                        )\n",
+                "                    )\n",
+                "                )\n",
+                "            )\n",
+                "            Return(\n",
+                "                Conditional(\n",
+                "                    condition=Var(\"x\")\n",
+                "                    then=Constant(1)\n",
+                "                    else=Constant(0)\n",
+                "                )\n",
+                "            )\n",
+                "        )\n",
+                "    )\n",
+                ")\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Resolution of the labels `goto` jumps to.
+//!
+//! Unlike variables, labels are not scoped by blocks: a label can be jumped to from
+//! anywhere in its function, including before it is defined. So each function is
+//! walked twice, first to collect its labels and then to check its `goto`s.
+
+use std::collections::HashMap;
+
+use parser::ast::{BlockItem, Declaration, Program, Statement};
+
+use crate::SemanticError;
+
+/// Renames every label in `program`, and the `goto`s that jump to it, to a name that
+/// is unique within the program.
+///
+/// Names look like `main.label.end`: the function the label is in, then the label as
+/// written. Errors on a label defined twice in the same function, or a `goto` to a
+/// label its function doesn't define.
+pub fn resolve_labels(program: &mut Program) -> Result<(), SemanticError> {
+    for declaration in &mut program.declarations {
+        if let Declaration::Function(function) = declaration
+            && let Some(body) = &mut function.body
+        {
+            let mut labels = HashMap::new();
+            collect_labels(body, &function.name, &mut labels)?;
+            resolve_gotos(body, &labels)?;
+        }
+    }
+    Ok(())
+}
+
+/// Renames the labels defined in `statement`, adding each one to `labels`.
+fn collect_labels(
+    statement: &mut Statement,
+    function_name: &str,
+    labels: &mut HashMap<String, String>,
+) -> Result<(), SemanticError> {
+    match statement {
+        Statement::Labeled { name, stmt } => {
+            if labels.contains_key(name) {
+                return Err(SemanticError::DuplicateLabel { name: name.clone() });
+            }
+            let unique_name = format!("{function_name}.label.{name}");
+            labels.insert(std::mem::replace(name, unique_name.clone()), unique_name);
+            collect_labels(stmt, function_name, labels)?;
+        }
+        Statement::If {
+            then, otherwise, ..
+        } => {
+            collect_labels(then, function_name, labels)?;
+            if let Some(otherwise) = otherwise {
+                collect_labels(otherwise, function_name, labels)?;
+            }
+        }
+        Statement::Compound(items) => {
+            for item in items {
+                if let BlockItem::Statement(statement) = item {
+                    collect_labels(statement, function_name, labels)?;
+                }
+            }
+        }
+        Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. }
+        | Statement::Switch { body, .. }
+        | Statement::Case { stmt: body, .. }
+        | Statement::Default { stmt: body, .. } => collect_labels(body, function_name, labels)?,
+        Statement::Return(_)
+        | Statement::Expression(_)
+        | Statement::Break { .. }
+        | Statement::Continue { .. }
+        | Statement::Goto(_)
+        | Statement::Null => {}
+    }
+    Ok(())
+}
+
+/// Renames the target of every `goto` in `statement` to the unique name of its label.
+fn resolve_gotos(
+    statement: &mut Statement,
+    labels: &HashMap<String, String>,
+) -> Result<(), SemanticError> {
+    match statement {
+        Statement::Goto(target) => {
+            let unique_name = labels
+                .get(target)
+                .ok_or_else(|| SemanticError::UndefinedLabel {
+                    name: target.clone(),
+                })?;
+            *target = unique_name.clone();
+        }
+        Statement::If {
+            then, otherwise, ..
+        } => {
+            resolve_gotos(then, labels)?;
+            if let Some(otherwise) = otherwise {
+                resolve_gotos(otherwise, labels)?;
+            }
+        }
+        Statement::Compound(items) => {
+            for item in items {
+                if let BlockItem::Statement(statement) = item {
+                    resolve_gotos(statement, labels)?;
+                }
+            }
+        }
+        Statement::Labeled { stmt: body, .. }
+        | Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. }
+        | Statement::Switch { body, .. }
+        | Statement::Case { stmt: body, .. }
+        | Statement::Default { stmt: body, .. } => resolve_gotos(body, labels)?,
+        Statement::Return(_)
+        | Statement::Expression(_)
+        | Statement::Break { .. }
+        | Statement::Continue { .. }
+        | Statement::Null => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve_labels_str(input: &str) -> Result<Program, SemanticError> {
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        resolve_labels(&mut program)?;
+        Ok(program)
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        let program = resolve_labels_str(
+            "int f(void) { end: return 0; }
+            int main(void) {
+                goto end;
+                if (1) { again: goto again; }
+                end: return 1;
+            }",
+        )
+        .unwrap();
+        let printed = parser::pretty::pretty_print(&program);
+        let names = printed
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("name=\"") && line.contains(".label."))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "name=\"f.label.end\"",
+                "name=\"main.label.again\"",
+                "name=\"main.label.end\"",
+            ]
+        );
+        let gotos = printed
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("Goto("))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gotos,
+            ["Goto(\"main.label.end\")", "Goto(\"main.label.again\")"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_labels_errors() {
+        assert_eq!(
+            resolve_labels_str("int main(void) { goto missing; return 0; }"),
+            Err(SemanticError::UndefinedLabel {
+                name: "missing".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_labels_str("int main(void) { a: ; { a: return 0; } }"),
+            Err(SemanticError::DuplicateLabel {
+                name: "a".to_string()
+            })
+        );
+        // Labels belong to a single function
+        assert_eq!(
+            resolve_labels_str("int f(void) { a: return 0; } int main(void) { goto a; }"),
+            Err(SemanticError::UndefinedLabel {
+                name: "a".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! Semantic analysis of the AST, between parsing and TACKY generation.
+
+use std::fmt;
+
+use errors::{DriverError, ExitCode};
+
+pub mod labels;
+pub mod loops;
+pub mod resolve;
+pub mod typecheck;
+
+pub use labels::resolve_labels;
+pub use loops::label_loops;
+pub use resolve::resolve;
+pub use typecheck::typecheck;
+
+#[derive(Debug, PartialEq)]
+pub enum SemanticError {
+    /// A variable or function was used without being declared
+    Undeclared {
+        /// Name as written in the source
+        name: String,
+    },
+    /// A variable was declared twice in the same scope
+    DuplicateDeclaration {
+        /// Name as written in the source
+        name: String,
+    },
+    /// Something other than a variable was assigned to
+    InvalidLvalue,
+    /// A function was defined inside another function
+    NestedFunctionDefinition {
+        /// Name of the inner function
+        name: String,
+    },
+    /// A `break` is not inside any loop or switch
+    BreakOutsideLoop,
+    /// A `continue` is not inside any loop
+    ContinueOutsideLoop,
+    /// A function was redeclared with different parameters, or a name was declared
+    /// as both a variable and a function
+    ConflictingDeclaration {
+        /// Name as written in the source
+        name: String,
+    },
+    /// A function was defined more than once
+    Redefinition {
+        /// Name of the function
+        name: String,
+    },
+    /// A function was called with a different number of arguments than it has
+    /// parameters
+    WrongArgumentCount {
+        /// Name of the function
+        function: String,
+        /// Number of parameters
+        expected: usize,
+        /// Number of arguments
+        found: usize,
+    },
+    /// A variable was called as if it were a function
+    CalledVariable {
+        /// Name as written in the source
+        name: String,
+    },
+    /// A function was used as a value, rather than called
+    FunctionUsedAsValue {
+        /// Name of the function
+        name: String,
+    },
+    /// A `case` is not inside any switch
+    CaseOutsideSwitch,
+    /// A `default` is not inside any switch
+    DefaultOutsideSwitch,
+    /// A switch has more than one case with the same value
+    DuplicateCase {
+        /// Value of the cases
+        value: i64,
+    },
+    /// A switch has more than one `default`
+    DuplicateDefault,
+    /// A label was defined more than once in the same function
+    DuplicateLabel {
+        /// Name as written in the source
+        name: String,
+    },
+    /// A `goto` jumps to a label that its function doesn't define
+    UndefinedLabel {
+        /// Name as written in the source
+        name: String,
+    },
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::Undeclared { name } => write!(f, "use of undeclared `{name}`"),
+            SemanticError::DuplicateDeclaration { name } => {
+                write!(f, "`{name}` is already declared in this scope")
+            }
+            SemanticError::InvalidLvalue => write!(f, "invalid assignment target"),
+            SemanticError::NestedFunctionDefinition { name } => {
+                write!(f, "function `{name}` is defined inside another function")
+            }
+            SemanticError::BreakOutsideLoop => {
+                write!(f, "`break` outside of a loop or switch")
+            }
+            SemanticError::ContinueOutsideLoop => write!(f, "`continue` outside of a loop"),
+            SemanticError::ConflictingDeclaration { name } => {
+                write!(f, "conflicting declarations of `{name}`")
+            }
+            SemanticError::Redefinition { name } => write!(f, "redefinition of `{name}`"),
+            SemanticError::WrongArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{function}` takes {expected} arguments, but {found} were given"
+            ),
+            SemanticError::CalledVariable { name } => {
+                write!(f, "called object `{name}` is not a function")
+            }
+            SemanticError::FunctionUsedAsValue { name } => {
+                write!(f, "function `{name}` used as a value")
+            }
+            SemanticError::CaseOutsideSwitch => write!(f, "`case` outside of a switch"),
+            SemanticError::DefaultOutsideSwitch => write!(f, "`default` outside of a switch"),
+            SemanticError::DuplicateCase { value } => {
+                write!(f, "duplicate case value `{value}` in switch")
+            }
+            SemanticError::DuplicateDefault => write!(f, "multiple `default` labels in one switch"),
+            SemanticError::DuplicateLabel { name } => {
+                write!(f, "label `{name}` is defined more than once")
+            }
+            SemanticError::UndefinedLabel { name } => {
+                write!(f, "`goto` to undefined label `{name}`")
+            }
+        }
+    }
+}
+
+impl From<SemanticError> for DriverError {
+    fn from(e: SemanticError) -> Self {
+        Self {
+            code: ExitCode::DataErr,
+            msg: format!("Failed semantic analysis: {e}."),
+            ..Default::default()
+        }
+    }
+}
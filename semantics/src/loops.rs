@@ -0,0 +1,328 @@
+//! Loop and switch labeling, following chapter 8 of the book.
+//!
+//! Every loop and `switch` gets a label that is unique within the program, and each
+//! `break` and `continue` is annotated with the label of the statement it belongs
+//! to, so that TACKY generation knows where they jump to. Each `case` and `default`
+//! gets a label too, which its `switch` collects so it can jump to them.
+
+use parser::ast::{BlockItem, Declaration, Program, Statement};
+
+use crate::SemanticError;
+
+/// Labels every loop and `switch` in `program`, and annotates each `break` with the
+/// label of its innermost enclosing loop or switch, and each `continue` with that of
+/// its innermost enclosing loop.
+///
+/// Labels look like `main.while.0`: the function the statement is in, the kind of
+/// statement, and a counter that restarts for every function. A case is labeled
+/// like `main.switch.1.case.0` and a default like `main.switch.1.default`. Errors on
+/// a `break` or `continue` outside of a loop, a `case` or `default` outside of a
+/// switch, and a switch with two cases of the same value or two defaults.
+pub fn label_loops(program: &mut Program) -> Result<(), SemanticError> {
+    for declaration in &mut program.declarations {
+        if let Declaration::Function(function) = declaration
+            && let Some(body) = &mut function.body
+        {
+            let mut labeler = LoopLabeler {
+                function_name: &function.name,
+                loops: 0,
+                enclosing: Vec::new(),
+            };
+            labeler.statement(body)?;
+        }
+    }
+    Ok(())
+}
+
+/// A loop or switch that `break` can jump out of
+enum Enclosing {
+    Loop(String),
+    Switch {
+        label: String,
+        /// Value (`None` for `default`) and label of each case found so far
+        cases: Vec<(Option<i64>, String)>,
+    },
+}
+
+impl Enclosing {
+    fn label(&self) -> &str {
+        match self {
+            Enclosing::Loop(label) | Enclosing::Switch { label, .. } => label,
+        }
+    }
+}
+
+/// Labels the loops and switches of a single function
+struct LoopLabeler<'a> {
+    function_name: &'a str,
+    /// Number of loops and switches labeled so far
+    loops: usize,
+    /// The enclosing loops and switches, innermost last
+    enclosing: Vec<Enclosing>,
+}
+
+impl LoopLabeler<'_> {
+    fn make_label(&mut self, kind: &str) -> String {
+        let label = format!("{}.{kind}.{}", self.function_name, self.loops);
+        self.loops += 1;
+        label
+    }
+
+    fn loop_body(&mut self, body: &mut Statement, label: &str) -> Result<(), SemanticError> {
+        self.enclosing.push(Enclosing::Loop(label.to_string()));
+        self.statement(body)?;
+        self.enclosing.pop();
+        Ok(())
+    }
+
+    /// Adds a case (or, if `value` is `None`, a default) to the innermost enclosing
+    /// switch, returning its label.
+    fn add_case(&mut self, value: Option<i64>) -> Result<String, SemanticError> {
+        let Some((switch_label, cases)) =
+            self.enclosing
+                .iter_mut()
+                .rev()
+                .find_map(|enclosing| match enclosing {
+                    Enclosing::Switch { label, cases } => Some((label, cases)),
+                    Enclosing::Loop(_) => None,
+                })
+        else {
+            return Err(match value {
+                Some(_) => SemanticError::CaseOutsideSwitch,
+                None => SemanticError::DefaultOutsideSwitch,
+            });
+        };
+        if cases.iter().any(|(existing, _)| *existing == value) {
+            return Err(match value {
+                Some(value) => SemanticError::DuplicateCase { value },
+                None => SemanticError::DuplicateDefault,
+            });
+        }
+        let label = match value {
+            Some(_) => format!("{switch_label}.case.{}", cases.len()),
+            None => format!("{switch_label}.default"),
+        };
+        cases.push((value, label.clone()));
+        Ok(label)
+    }
+
+    fn statement(&mut self, statement: &mut Statement) -> Result<(), SemanticError> {
+        match statement {
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                self.statement(then)?;
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise)?;
+                }
+            }
+            Statement::Compound(items) => {
+                for item in items {
+                    if let BlockItem::Statement(statement) = item {
+                        self.statement(statement)?;
+                    }
+                }
+            }
+            Statement::Break { label } => {
+                let enclosing = self
+                    .enclosing
+                    .last()
+                    .ok_or(SemanticError::BreakOutsideLoop)?;
+                *label = Some(enclosing.label().to_string());
+            }
+            Statement::Continue { label } => {
+                let enclosing = self
+                    .enclosing
+                    .iter()
+                    .rev()
+                    .find_map(|enclosing| match enclosing {
+                        Enclosing::Loop(label) => Some(label),
+                        Enclosing::Switch { .. } => None,
+                    })
+                    .ok_or(SemanticError::ContinueOutsideLoop)?;
+                *label = Some(enclosing.clone());
+            }
+            Statement::While { body, label, .. } => {
+                let new_label = self.make_label("while");
+                self.loop_body(body, &new_label)?;
+                *label = Some(new_label);
+            }
+            Statement::DoWhile { body, label, .. } => {
+                let new_label = self.make_label("do");
+                self.loop_body(body, &new_label)?;
+                *label = Some(new_label);
+            }
+            Statement::For { body, label, .. } => {
+                let new_label = self.make_label("for");
+                self.loop_body(body, &new_label)?;
+                *label = Some(new_label);
+            }
+            Statement::Switch {
+                body, label, cases, ..
+            } => {
+                let new_label = self.make_label("switch");
+                self.enclosing.push(Enclosing::Switch {
+                    label: new_label.clone(),
+                    cases: Vec::new(),
+                });
+                self.statement(body)?;
+                if let Some(Enclosing::Switch {
+                    cases: found_cases, ..
+                }) = self.enclosing.pop()
+                {
+                    *cases = found_cases;
+                }
+                *label = Some(new_label);
+            }
+            Statement::Case { value, stmt, label } => {
+                *label = Some(self.add_case(Some(*value))?);
+                self.statement(stmt)?;
+            }
+            Statement::Default { stmt, label } => {
+                *label = Some(self.add_case(None)?);
+                self.statement(stmt)?;
+            }
+            Statement::Labeled { stmt, .. } => self.statement(stmt)?,
+            Statement::Return(_)
+            | Statement::Expression(_)
+            | Statement::Goto(_)
+            | Statement::Null => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label_loops_str(input: &str) -> Result<Program, SemanticError> {
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        label_loops(&mut program)?;
+        Ok(program)
+    }
+
+    #[test]
+    fn test_label_nested_loops() {
+        let program = label_loops_str(
+            "int f(void) { while (1) break; return 0; }
+            int main(void) {
+                for (;;) {
+                    do { if (1) continue; break; } while (0);
+                    continue;
+                }
+                return 0;
+            }",
+        )
+        .unwrap();
+        // Labeled statements print as e.g. `body=Break[main.while.0]`
+        let labels = parser::pretty::pretty_print(&program)
+            .lines()
+            .filter_map(|line| {
+                let (statement, rest) = line.split_once('[')?;
+                let statement = statement.rsplit(['=', ' ']).next()?;
+                let (label, _) = rest.split_once(']')?;
+                Some(format!("{statement} {label}"))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [
+                "While f.while.0",
+                "Break f.while.0",
+                "For main.for.0",
+                "DoWhile main.do.1",
+                "Continue main.do.1",
+                "Break main.do.1",
+                "Continue main.for.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_label_loops_errors() {
+        assert_eq!(
+            label_loops_str("int main(void) { break; }"),
+            Err(SemanticError::BreakOutsideLoop)
+        );
+        assert_eq!(
+            label_loops_str("int main(void) { while (1) ; if (1) continue; }"),
+            Err(SemanticError::ContinueOutsideLoop)
+        );
+        // A switch can be broken out of, but not continued
+        assert_eq!(
+            label_loops_str("int main(void) { switch (1) { continue; } }"),
+            Err(SemanticError::ContinueOutsideLoop)
+        );
+    }
+
+    #[test]
+    fn test_label_switch() {
+        let program = label_loops_str(
+            "int main(void) {
+                while (1)
+                    switch (x) {
+                        case 1: continue;
+                        default: break;
+                        case 2: if (1) { case 3: ; }
+                    }
+                return 0;
+            }",
+        )
+        .unwrap();
+        let [Declaration::Function(main)] = program.declarations.as_slice() else {
+            panic!("expected a single function");
+        };
+        let Some(Statement::Compound(items)) = &main.body else {
+            panic!("expected a body");
+        };
+        let BlockItem::Statement(Statement::While { body, .. }) = &items[0] else {
+            panic!("expected a loop");
+        };
+        let Statement::Switch { label, cases, .. } = body.as_ref() else {
+            panic!("expected a switch");
+        };
+        assert_eq!(label.as_deref(), Some("main.switch.1"));
+        assert_eq!(
+            cases,
+            &[
+                (Some(1), "main.switch.1.case.0".to_string()),
+                (None, "main.switch.1.default".to_string()),
+                (Some(2), "main.switch.1.case.2".to_string()),
+                (Some(3), "main.switch.1.case.3".to_string()),
+            ]
+        );
+        let printed = parser::pretty::pretty_print(&program);
+        assert!(printed.contains("Continue[main.while.0]"));
+        assert!(printed.contains("Break[main.switch.1]"));
+    }
+
+    #[test]
+    fn test_label_switch_errors() {
+        assert_eq!(
+            label_loops_str("int main(void) { case 1: return 0; }"),
+            Err(SemanticError::CaseOutsideSwitch)
+        );
+        assert_eq!(
+            label_loops_str("int main(void) { default: return 0; }"),
+            Err(SemanticError::DefaultOutsideSwitch)
+        );
+        assert_eq!(
+            label_loops_str("int main(void) { switch (x) { case 1: case 2 - 1: ; } }"),
+            Err(SemanticError::DuplicateCase { value: 1 })
+        );
+        // Values are compared once converted to `int`
+        assert_eq!(
+            label_loops_str("int main(void) { switch (x) { case 1: case 4294967297: ; } }"),
+            Err(SemanticError::DuplicateCase { value: 1 })
+        );
+        assert_eq!(
+            label_loops_str("int main(void) { switch (x) { default: default: ; } }"),
+            Err(SemanticError::DuplicateDefault)
+        );
+        // Each switch has its own cases
+        assert!(
+            label_loops_str("int main(void) { switch (x) case 1: switch (y) case 1: ; }").is_ok()
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! Identifier resolution, following chapter 5 onwards of the book.
+//!
+//! Every local variable is renamed to a name that is unique within the program,
+//! so that later passes don't need to know about scopes. Function names are left
+//! as they are, since they refer to the same function in every scope.
+
+use std::collections::HashMap;
+
+use parser::ast::{
+    BlockItem, Declaration, Expression, ForInit, FunctionDefinition, Program, Statement,
+    VariableDeclaration,
+};
+
+use crate::SemanticError;
+
+/// Resolves every identifier in `program` to the declaration it refers to,
+/// renaming local variables to unique names.
+///
+/// Errors on the use of an undeclared identifier, on a variable declared twice in
+/// the same scope, and on assignments to something other than a variable.
+pub fn resolve(program: &mut Program) -> Result<(), SemanticError> {
+    let mut resolver = Resolver {
+        scopes: vec![HashMap::new()],
+        variables: 0,
+    };
+    for declaration in &mut program.declarations {
+        match declaration {
+            Declaration::Function(function) => resolver.function(function)?,
+            Declaration::Variable(declaration) => resolver.variable_declaration(declaration)?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name that `unique_name`, as given by [`resolve`], had in the source.
+pub fn source_name(unique_name: &str) -> &str {
+    // Identifiers can't contain a `.`, so the first one starts what `resolve` added
+    unique_name
+        .split_once('.')
+        .map_or(unique_name, |(name, _)| name)
+}
+
+/// What an identifier in scope refers to
+struct Entry {
+    /// Name to replace the identifier with
+    unique_name: String,
+    /// Whether the identifier is a function, which may be redeclared in the same scope
+    has_linkage: bool,
+}
+
+struct Resolver {
+    /// Identifiers declared in each enclosing scope, innermost last
+    scopes: Vec<HashMap<String, Entry>>,
+    /// Number of variables renamed so far
+    variables: usize,
+}
+
+impl Resolver {
+    fn current_scope(&mut self) -> &mut HashMap<String, Entry> {
+        // Unwrap is safe, as the file scope is never popped
+        self.scopes.last_mut().unwrap()
+    }
+
+    fn lookup(&self, name: &str) -> Result<&str, SemanticError> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|entry| entry.unique_name.as_str())
+            .ok_or_else(|| SemanticError::Undeclared {
+                name: name.to_string(),
+            })
+    }
+
+    /// Declares a variable in the current scope, returning its unique name.
+    fn declare_variable(&mut self, name: &str) -> Result<String, SemanticError> {
+        if self.current_scope().contains_key(name) {
+            return Err(SemanticError::DuplicateDeclaration {
+                name: name.to_string(),
+            });
+        }
+
+        // Tacky temporaries are named `tmp.N`, so the `local` keeps a variable
+        // named `tmp` from colliding with them
+        let unique_name = format!("{name}.local.{}", self.variables);
+        self.variables += 1;
+        self.current_scope().insert(
+            name.to_string(),
+            Entry {
+                unique_name: unique_name.clone(),
+                has_linkage: false,
+            },
+        );
+        Ok(unique_name)
+    }
+
+    fn function(&mut self, function: &mut FunctionDefinition) -> Result<(), SemanticError> {
+        if self
+            .current_scope()
+            .get(&function.name)
+            .is_some_and(|entry| !entry.has_linkage)
+        {
+            return Err(SemanticError::DuplicateDeclaration {
+                name: function.name.clone(),
+            });
+        }
+        self.current_scope().insert(
+            function.name.clone(),
+            Entry {
+                unique_name: function.name.clone(),
+                has_linkage: true,
+            },
+        );
+
+        // The parameters are in the same scope as the outermost block of the body
+        self.scopes.push(HashMap::new());
+        for (_, name) in &mut function.params {
+            if let Some(name) = name {
+                *name = self.declare_variable(name)?;
+            }
+        }
+        if let Some(Statement::Compound(items)) = &mut function.body {
+            for item in items {
+                self.block_item(item)?;
+            }
+        }
+        self.scopes.pop();
+
+        Ok(())
+    }
+
+    fn variable_declaration(
+        &mut self,
+        declaration: &mut VariableDeclaration,
+    ) -> Result<(), SemanticError> {
+        declaration.name = self.declare_variable(&declaration.name)?;
+        // The variable is in scope in its own initializer
+        if let Some(init) = &mut declaration.init {
+            self.expression(init)?;
+        }
+        Ok(())
+    }
+
+    fn block_item(&mut self, item: &mut BlockItem) -> Result<(), SemanticError> {
+        match item {
+            BlockItem::Statement(statement) => self.statement(statement),
+            BlockItem::Declaration(Declaration::Variable(declaration)) => {
+                self.variable_declaration(declaration)
+            }
+            BlockItem::Declaration(Declaration::Function(function)) => {
+                if function.body.is_some() {
+                    return Err(SemanticError::NestedFunctionDefinition {
+                        name: function.name.clone(),
+                    });
+                }
+                self.function(function)
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) -> Result<(), SemanticError> {
+        match statement {
+            Statement::Return(exp) | Statement::Expression(exp) => self.expression(exp)?,
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition)?;
+                self.statement(then)?;
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise)?;
+                }
+            }
+            Statement::Compound(items) => {
+                self.scopes.push(HashMap::new());
+                for item in items {
+                    self.block_item(item)?;
+                }
+                self.scopes.pop();
+            }
+            Statement::Break { .. }
+            | Statement::Continue { .. }
+            | Statement::Goto(_)
+            | Statement::Null => {}
+            Statement::Labeled { stmt, .. }
+            | Statement::Case { stmt, .. }
+            | Statement::Default { stmt, .. } => self.statement(stmt)?,
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile {
+                body, condition, ..
+            }
+            | Statement::Switch {
+                control: condition,
+                body,
+                ..
+            } => {
+                self.expression(condition)?;
+                self.statement(body)?;
+            }
+            Statement::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                // A declaration in the header is in a scope of its own
+                self.scopes.push(HashMap::new());
+                match init {
+                    ForInit::Declaration(declaration) => self.variable_declaration(declaration)?,
+                    ForInit::Expression(Some(exp)) => self.expression(exp)?,
+                    ForInit::Expression(None) => {}
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition)?;
+                }
+                if let Some(post) = post {
+                    self.expression(post)?;
+                }
+                self.statement(body)?;
+                self.scopes.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, exp: &mut Expression) -> Result<(), SemanticError> {
+        match exp {
+            Expression::Constant(_) => {}
+            Expression::Var(name) => *name = self.lookup(name)?.to_string(),
+            Expression::Unary { expr, .. } | Expression::Cast { expr, .. } => {
+                self.expression(expr)?
+            }
+            Expression::Binary { left, right, .. }
+            | Expression::Expect {
+                expr: left,
+                expected: right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+            }
+            Expression::Assignment { target, value } => {
+                if !matches!(**target, Expression::Var(_)) {
+                    return Err(SemanticError::InvalidLvalue);
+                }
+                self.expression(target)?;
+                self.expression(value)?;
+            }
+            Expression::Conditional {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.expression(cond)?;
+                self.expression(then)?;
+                self.expression(otherwise)?;
+            }
+            Expression::FunctionCall { name, args } => {
+                *name = self.lookup(name)?.to_string();
+                for arg in args {
+                    self.expression(arg)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::{DriverError, ExitCode};
+
+    fn resolve_str(input: &str) -> Result<Program, SemanticError> {
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        resolve(&mut program)?;
+        Ok(program)
+    }
+
+    /// Returns the names of the declarations and variables in `program`, in order.
+    fn names(program: &Program) -> Vec<String> {
+        parser::pretty::pretty_print(program)
+            .lines()
+            .filter_map(|line| {
+                let (_, rest) = line.split_once('"')?;
+                let (name, _) = rest.rsplit_once('"')?;
+                Some(name.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_shadowing() {
+        let program = resolve_str(
+            "int main(void) {
+                int x = 1;
+                { int x = x + 1; x = 3; }
+                for (int x = 0; x; ) ;
+                return x;
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            names(&program),
+            [
+                "main",
+                "x.local.0",
+                "x.local.1",
+                "x.local.1",
+                "x.local.1",
+                "x.local.2",
+                "x.local.2",
+                "x.local.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_functions() {
+        let program = resolve_str(
+            "int f(int a, int b);
+            int f(int a, int b) { return a + b; }
+            int main(void) { int f(int, int); int tmp = 2; return f(tmp, 1); }",
+        )
+        .unwrap();
+        let Declaration::Function(f) = &program.declarations[1] else {
+            panic!("expected a function: {:?}", program.declarations[1]);
+        };
+        assert_eq!(
+            f.params
+                .iter()
+                .map(|(_, name)| name.as_deref())
+                .collect::<Vec<_>>(),
+            [Some("a.local.2"), Some("b.local.3")]
+        );
+        assert_eq!(
+            names(&program),
+            [
+                "f",
+                "f",
+                "a.local.2",
+                "b.local.3",
+                "main",
+                "f",
+                "tmp.local.4",
+                "f",
+                "tmp.local.4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        assert_eq!(
+            resolve_str("int main(void) { return x; }"),
+            Err(SemanticError::Undeclared {
+                name: "x".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int main(void) { { int x = 1; } return x; }"),
+            Err(SemanticError::Undeclared {
+                name: "x".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int main(void) { return f(); }"),
+            Err(SemanticError::Undeclared {
+                name: "f".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int main(void) { int x; int x; return 0; }"),
+            Err(SemanticError::DuplicateDeclaration {
+                name: "x".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int f(int a) { int a; return a; }"),
+            Err(SemanticError::DuplicateDeclaration {
+                name: "a".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int main(void) { int f; int f(void); return 0; }"),
+            Err(SemanticError::DuplicateDeclaration {
+                name: "f".to_string()
+            })
+        );
+        assert_eq!(
+            resolve_str("int main(void) { int x; 2 = x; return 0; }"),
+            Err(SemanticError::InvalidLvalue)
+        );
+        assert_eq!(
+            resolve_str("int main(void) { int f(void) { return 1; } return 0; }"),
+            Err(SemanticError::NestedFunctionDefinition {
+                name: "f".to_string()
+            })
+        );
+        assert_eq!(
+            DriverError::from(SemanticError::Undeclared {
+                name: "x".to_string()
+            }),
+            DriverError {
+                code: ExitCode::DataErr,
+                msg: "Failed semantic analysis: use of undeclared `x`.".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+}
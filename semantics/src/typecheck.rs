@@ -0,0 +1,367 @@
+//! Type checking, following chapter 9 onwards of the book.
+//!
+//! Every value is currently an `int`, so this only checks that functions and
+//! variables are used as such, and that calls match the declared parameters.
+
+use std::collections::HashMap;
+
+use parser::ast::{
+    BlockItem, Declaration, Expression, ForInit, FunctionDefinition, Program, Statement, Type,
+    VariableDeclaration,
+};
+
+use crate::SemanticError;
+use crate::resolve::source_name;
+
+/// What a name refers to
+#[derive(Clone, Debug, PartialEq)]
+pub enum SymbolInfo {
+    Variable {
+        ty: Type,
+    },
+    Function {
+        /// Parameter types, which are only meaningful if `has_prototype` is set
+        params: Vec<Type>,
+        /// Whether a declaration gave the parameters, so calls must match them
+        has_prototype: bool,
+        /// Whether the function has a body
+        defined: bool,
+    },
+}
+
+/// Information about every variable and function, keyed by the names given by
+/// [`resolve`](crate::resolve)
+pub type SymbolTable = HashMap<String, SymbolInfo>;
+
+/// Type checks `program`, which must have been through [`resolve`](crate::resolve),
+/// returning the symbol table it builds.
+///
+/// Errors on a function redeclared with different parameters or defined twice, on
+/// a call with the wrong number of arguments, and on calling a variable or using a
+/// function as a value. Calls to a function declared with `()` may pass any number
+/// of arguments, unlike one declared with `(void)`.
+pub fn typecheck(program: &Program) -> Result<SymbolTable, SemanticError> {
+    let mut checker = TypeChecker {
+        symbols: SymbolTable::new(),
+    };
+    for declaration in &program.declarations {
+        checker.declaration(declaration)?;
+    }
+    Ok(checker.symbols)
+}
+
+struct TypeChecker {
+    symbols: SymbolTable,
+}
+
+impl TypeChecker {
+    fn declaration(&mut self, declaration: &Declaration) -> Result<(), SemanticError> {
+        match declaration {
+            Declaration::Variable(declaration) => self.variable_declaration(declaration),
+            Declaration::Function(function) => self.function(function),
+        }
+    }
+
+    fn variable_declaration(
+        &mut self,
+        declaration: &VariableDeclaration,
+    ) -> Result<(), SemanticError> {
+        self.symbols.insert(
+            declaration.name.clone(),
+            SymbolInfo::Variable { ty: Type::Int },
+        );
+        if let Some(init) = &declaration.init {
+            self.expression(init)?;
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, function: &FunctionDefinition) -> Result<(), SemanticError> {
+        let params = function
+            .params
+            .iter()
+            .map(|(ty, _)| ty.clone())
+            .collect::<Vec<_>>();
+        let mut has_prototype = function.has_prototype;
+        let mut defined = function.body.is_some();
+
+        if let Some(previous) = self.symbols.get(&function.name) {
+            let SymbolInfo::Function {
+                params: previous_params,
+                has_prototype: previous_has_prototype,
+                defined: previous_defined,
+            } = previous
+            else {
+                return Err(SemanticError::ConflictingDeclaration {
+                    name: source_name(&function.name).to_string(),
+                });
+            };
+            // Without a prototype, a declaration says nothing about the parameters
+            if has_prototype && *previous_has_prototype && params != *previous_params {
+                return Err(SemanticError::ConflictingDeclaration {
+                    name: source_name(&function.name).to_string(),
+                });
+            }
+            if defined && *previous_defined {
+                return Err(SemanticError::Redefinition {
+                    name: function.name.clone(),
+                });
+            }
+            has_prototype |= previous_has_prototype;
+            defined |= previous_defined;
+        }
+
+        // Keep the parameters of whichever declaration gave them
+        let params = match self.symbols.get(&function.name) {
+            Some(SymbolInfo::Function {
+                params: previous_params,
+                has_prototype: true,
+                ..
+            }) if !function.has_prototype => previous_params.clone(),
+            _ => params,
+        };
+        self.symbols.insert(
+            function.name.clone(),
+            SymbolInfo::Function {
+                params,
+                has_prototype,
+                defined,
+            },
+        );
+
+        for (ty, name) in &function.params {
+            if let Some(name) = name {
+                self.symbols
+                    .insert(name.clone(), SymbolInfo::Variable { ty: ty.clone() });
+            }
+        }
+        if let Some(body) = &function.body {
+            self.statement(body)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), SemanticError> {
+        match statement {
+            Statement::Return(exp) | Statement::Expression(exp) => self.expression(exp)?,
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expression(condition)?;
+                self.statement(then)?;
+                if let Some(otherwise) = otherwise {
+                    self.statement(otherwise)?;
+                }
+            }
+            Statement::Compound(items) => {
+                for item in items {
+                    match item {
+                        BlockItem::Statement(statement) => self.statement(statement)?,
+                        BlockItem::Declaration(declaration) => self.declaration(declaration)?,
+                    }
+                }
+            }
+            Statement::Break { .. }
+            | Statement::Continue { .. }
+            | Statement::Goto(_)
+            | Statement::Null => {}
+            Statement::Labeled { stmt, .. }
+            | Statement::Case { stmt, .. }
+            | Statement::Default { stmt, .. } => self.statement(stmt)?,
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile {
+                body, condition, ..
+            }
+            | Statement::Switch {
+                control: condition,
+                body,
+                ..
+            } => {
+                self.expression(condition)?;
+                self.statement(body)?;
+            }
+            Statement::For {
+                init,
+                condition,
+                post,
+                body,
+                ..
+            } => {
+                match init {
+                    ForInit::Declaration(declaration) => self.variable_declaration(declaration)?,
+                    ForInit::Expression(Some(exp)) => self.expression(exp)?,
+                    ForInit::Expression(None) => {}
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition)?;
+                }
+                if let Some(post) = post {
+                    self.expression(post)?;
+                }
+                self.statement(body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&self, exp: &Expression) -> Result<(), SemanticError> {
+        match exp {
+            Expression::Constant(_) => {}
+            Expression::Var(name) => {
+                if let Some(SymbolInfo::Function { .. }) = self.symbols.get(name) {
+                    return Err(SemanticError::FunctionUsedAsValue { name: name.clone() });
+                }
+            }
+            Expression::Unary { expr, .. } | Expression::Cast { expr, .. } => {
+                self.expression(expr)?
+            }
+            Expression::Binary { left, right, .. }
+            | Expression::Assignment {
+                target: left,
+                value: right,
+            }
+            | Expression::Expect {
+                expr: left,
+                expected: right,
+            } => {
+                self.expression(left)?;
+                self.expression(right)?;
+            }
+            Expression::Conditional {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.expression(cond)?;
+                self.expression(then)?;
+                self.expression(otherwise)?;
+            }
+            Expression::FunctionCall { name, args } => {
+                match self.symbols.get(name) {
+                    Some(SymbolInfo::Function {
+                        params,
+                        has_prototype: true,
+                        ..
+                    }) if params.len() != args.len() => {
+                        return Err(SemanticError::WrongArgumentCount {
+                            function: name.clone(),
+                            expected: params.len(),
+                            found: args.len(),
+                        });
+                    }
+                    Some(SymbolInfo::Variable { .. }) => {
+                        return Err(SemanticError::CalledVariable {
+                            name: source_name(name).to_string(),
+                        });
+                    }
+                    _ => {}
+                }
+                for arg in args {
+                    self.expression(arg)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typecheck_str(input: &str) -> Result<SymbolTable, SemanticError> {
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        crate::resolve(&mut program)?;
+        typecheck(&program)
+    }
+
+    #[test]
+    fn test_typecheck_symbols() {
+        let symbols = typecheck_str(
+            "int f();
+            int f(int a, long b) { return a; }
+            int f();
+            int main(void) { int x = f(1, 2); return x; }",
+        )
+        .unwrap();
+        assert_eq!(
+            symbols["f"],
+            SymbolInfo::Function {
+                params: vec![Type::Int, Type::Long],
+                has_prototype: true,
+                defined: true,
+            }
+        );
+        assert_eq!(
+            symbols["b.local.1"],
+            SymbolInfo::Variable { ty: Type::Long }
+        );
+        assert_eq!(symbols["x.local.2"], SymbolInfo::Variable { ty: Type::Int });
+    }
+
+    #[test]
+    fn test_typecheck_argument_count() {
+        assert_eq!(
+            typecheck_str("int f(int a, int b); int main(void) { return f(1); }"),
+            Err(SemanticError::WrongArgumentCount {
+                function: "f".to_string(),
+                expected: 2,
+                found: 1,
+            })
+        );
+        assert_eq!(
+            typecheck_str("int f(void); int main(void) { return f(1); }"),
+            Err(SemanticError::WrongArgumentCount {
+                function: "f".to_string(),
+                expected: 0,
+                found: 1,
+            })
+        );
+        // Without a prototype, any arguments are allowed
+        assert!(typecheck_str("int f(); int main(void) { return f(1) + f(); }").is_ok());
+    }
+
+    #[test]
+    fn test_typecheck_errors() {
+        let err = typecheck_str("int f(int a); int f(int a, int b);").unwrap_err();
+        assert_eq!(
+            err,
+            SemanticError::ConflictingDeclaration {
+                name: "f".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "conflicting declarations of `f`");
+        assert_eq!(
+            typecheck_str("int f(int a); int f(long a);"),
+            Err(SemanticError::ConflictingDeclaration {
+                name: "f".to_string()
+            })
+        );
+        assert!(typecheck_str("int f(int a); int f(); int f(int b) { return b; }").is_ok());
+        assert_eq!(
+            typecheck_str("int f(void) { return 1; } int f(void) { return 2; }"),
+            Err(SemanticError::Redefinition {
+                name: "f".to_string()
+            })
+        );
+        // Errors name variables as written, not as renamed by resolution
+        let err = typecheck_str("int main(void) { int x = 1; return x(); }").unwrap_err();
+        assert_eq!(
+            err,
+            SemanticError::CalledVariable {
+                name: "x".to_string()
+            }
+        );
+        assert_eq!(err.to_string(), "called object `x` is not a function");
+        assert_eq!(
+            typecheck_str("int f(void); int main(void) { return f + 1; }"),
+            Err(SemanticError::FunctionUsedAsValue {
+                name: "f".to_string()
+            })
+        );
+    }
+}
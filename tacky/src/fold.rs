@@ -0,0 +1,167 @@
+//! Constant folding of TACKY.
+
+use std::collections::HashMap;
+
+use crate::ir::{BinaryOp, Instruction, TackyProgram, UnaryOp, Val};
+
+/// Evaluates every `Unary` and `Binary` instruction whose operands are constants,
+/// removing the instruction and replacing uses of its result with the constant.
+/// So `return 2 + 3;` lowers to `Return(Constant(5))`.
+///
+/// Operations that would be undefined at run time, like division by zero or an
+/// overflowing `int`, are left for the program to perform.
+///
+/// This relies on lowering writing the result of every `Unary` and `Binary` to a
+/// fresh temporary, which is assigned nowhere else and only used after it.
+pub fn fold_constants(program: &mut TackyProgram) {
+    for function in &mut program.functions {
+        let mut folded = HashMap::new();
+        let body = std::mem::take(&mut function.body);
+        for mut instruction in body {
+            substitute(&mut instruction, &folded);
+            let result = match &instruction {
+                Instruction::Unary {
+                    op,
+                    src: Val::Constant(src),
+                    dst: Val::Var(dst),
+                } => fold_unary(*op, *src).map(|value| (dst, value)),
+                Instruction::Binary {
+                    op,
+                    src1: Val::Constant(src1),
+                    src2: Val::Constant(src2),
+                    dst: Val::Var(dst),
+                } => fold_binary(*op, *src1, *src2).map(|value| (dst, value)),
+                _ => None,
+            };
+            match result {
+                Some((dst, value)) => {
+                    folded.insert(dst.clone(), value);
+                }
+                None => function.body.push(instruction),
+            }
+        }
+    }
+}
+
+/// Replaces the operands of `instruction` that have been folded into constants.
+fn substitute(instruction: &mut Instruction, folded: &HashMap<String, i64>) {
+    let substitute_val = |val: &mut Val| {
+        if let Val::Var(name) = val
+            && let Some(value) = folded.get(name)
+        {
+            *val = Val::Constant(*value);
+        }
+    };
+    match instruction {
+        Instruction::Return(src)
+        | Instruction::Unary { src, .. }
+        | Instruction::Copy { src, .. }
+        | Instruction::JumpIfZero { condition: src, .. }
+        | Instruction::JumpIfNotZero { condition: src, .. } => substitute_val(src),
+        Instruction::Binary { src1, src2, .. } => {
+            substitute_val(src1);
+            substitute_val(src2);
+        }
+        Instruction::FunCall { args, .. } => args.iter_mut().for_each(substitute_val),
+        Instruction::Jump(_) | Instruction::Label(_) => {}
+    }
+}
+
+/// Evaluates a unary operation on an `int`, or returns `None` if it can't be folded.
+fn fold_unary(op: UnaryOp, src: i64) -> Option<i64> {
+    let src = i32::try_from(src).ok()?;
+    let value = match op {
+        UnaryOp::Negate => src.checked_neg()?,
+        UnaryOp::Complement => !src,
+        UnaryOp::Not => (src == 0).into(),
+    };
+    Some(value.into())
+}
+
+/// Evaluates a binary operation on `int`s, or returns `None` if it can't be folded.
+fn fold_binary(op: BinaryOp, src1: i64, src2: i64) -> Option<i64> {
+    let src1 = i32::try_from(src1).ok()?;
+    let src2 = i32::try_from(src2).ok()?;
+    let value = match op {
+        BinaryOp::Add => src1.checked_add(src2)?,
+        BinaryOp::Subtract => src1.checked_sub(src2)?,
+        BinaryOp::Multiply => src1.checked_mul(src2)?,
+        BinaryOp::Divide => src1.checked_div(src2)?,
+        BinaryOp::Remainder => src1.checked_rem(src2)?,
+        BinaryOp::BitwiseAnd => src1 & src2,
+        BinaryOp::BitwiseOr => src1 | src2,
+        BinaryOp::BitwiseXor => src1 ^ src2,
+        BinaryOp::LeftShift => src1.checked_shl(u32::try_from(src2).ok()?)?,
+        BinaryOp::RightShift => src1.checked_shr(u32::try_from(src2).ok()?)?,
+        BinaryOp::Equal => (src1 == src2).into(),
+        BinaryOp::NotEqual => (src1 != src2).into(),
+        BinaryOp::LessThan => (src1 < src2).into(),
+        BinaryOp::LessOrEqual => (src1 <= src2).into(),
+        BinaryOp::GreaterThan => (src1 > src2).into(),
+        BinaryOp::GreaterOrEqual => (src1 >= src2).into(),
+    };
+    Some(value.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lowers and folds `body` as the body of `main`, and returns its instructions
+    fn folded_body(body: &str) -> Vec<Instruction> {
+        let input = format!("int main(void) {{ {body} }}");
+        let mut program = parser::parse(lexer::tokenize_str(&input).unwrap()).unwrap();
+        semantics::label_loops(&mut program).unwrap();
+        let mut tacky = crate::gen_tacky(&program);
+        fold_constants(&mut tacky);
+        tacky.functions.remove(0).body
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(
+            folded_body("return -(-5);"),
+            [Instruction::Return(Val::Constant(5))]
+        );
+        assert_eq!(
+            folded_body("return 2 + 3 * 4 == 14;"),
+            [Instruction::Return(Val::Constant(1))]
+        );
+        // Only the constant operand is folded
+        assert_eq!(
+            folded_body("int x = 1; return x + (2 << 3);"),
+            [
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: Val::Var("x".to_string()),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src1: Val::Var("x".to_string()),
+                    src2: Val::Constant(16),
+                    dst: Val::Var("tmp.1".to_string()),
+                },
+                Instruction::Return(Val::Var("tmp.1".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold_constants_undefined() {
+        let divide_by_zero = Instruction::Binary {
+            op: BinaryOp::Divide,
+            src1: Val::Constant(1),
+            src2: Val::Constant(0),
+            dst: Val::Var("tmp.0".to_string()),
+        };
+        assert_eq!(
+            folded_body("return 1 / 0;"),
+            [
+                divide_by_zero,
+                Instruction::Return(Val::Var("tmp.0".to_string())),
+            ]
+        );
+        assert_eq!(folded_body("return 2147483647 + 1;").len(), 2);
+        assert_eq!(folded_body("return 1 << 32;").len(), 2);
+    }
+}
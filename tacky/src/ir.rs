@@ -0,0 +1,214 @@
+//! The TACKY intermediate representation: a flat, three-address-code instruction
+//! list per function.
+//!
+//! Every type displays in a readable, C-like form, for debugging lowering.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TackyProgram {
+    pub functions: Vec<Function>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Instruction>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Return(Val),
+    Unary {
+        op: UnaryOp,
+        src: Val,
+        dst: Val,
+    },
+    Binary {
+        op: BinaryOp,
+        src1: Val,
+        src2: Val,
+        dst: Val,
+    },
+    Copy {
+        src: Val,
+        dst: Val,
+    },
+    Jump(String),
+    JumpIfZero {
+        condition: Val,
+        target: String,
+    },
+    JumpIfNotZero {
+        condition: Val,
+        target: String,
+    },
+    Label(String),
+    FunCall {
+        name: String,
+        args: Vec<Val>,
+        dst: Val,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Val {
+    Constant(i64),
+    /// A named variable, or a temporary introduced during lowering
+    Var(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Complement,
+    Not,
+}
+
+/// Binary operators that survive lowering. `&&` and `||` become jumps instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    LeftShift,
+    RightShift,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+impl fmt::Display for TackyProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A function displays as its signature, with its instructions on the lines
+/// beneath, indented apart from labels, which are outdented so jump targets
+/// stand out.
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}({}):", self.name, self.params.join(", "))?;
+        for instruction in &self.body {
+            match instruction {
+                Instruction::Label(_) => writeln!(f, "  {instruction}")?,
+                _ => writeln!(f, "    {instruction}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Return(val) => write!(f, "return {val}"),
+            Instruction::Unary { op, src, dst } => write!(f, "{dst} = {op}{src}"),
+            Instruction::Binary {
+                op,
+                src1,
+                src2,
+                dst,
+            } => write!(f, "{dst} = {src1} {op} {src2}"),
+            Instruction::Copy { src, dst } => write!(f, "{dst} = {src}"),
+            Instruction::Jump(target) => write!(f, "jump {target}"),
+            Instruction::JumpIfZero { condition, target } => {
+                write!(f, "jump_if_zero {condition}, {target}")
+            }
+            Instruction::JumpIfNotZero { condition, target } => {
+                write!(f, "jump_if_not_zero {condition}, {target}")
+            }
+            Instruction::Label(label) => write!(f, "{label}:"),
+            Instruction::FunCall { name, args, dst } => {
+                let args = args.iter().map(Val::to_string).collect::<Vec<_>>();
+                write!(f, "{dst} = {name}({})", args.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Val::Constant(value) => write!(f, "{value}"),
+            Val::Var(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::Complement => "~",
+            UnaryOp::Not => "!",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Remainder => "%",
+            BinaryOp::BitwiseAnd => "&",
+            BinaryOp::BitwiseOr => "|",
+            BinaryOp::BitwiseXor => "^",
+            BinaryOp::LeftShift => "<<",
+            BinaryOp::RightShift => ">>",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::LessThan => "<",
+            BinaryOp::LessOrEqual => "<=",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::GreaterOrEqual => ">=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_display() {
+        let input = "int add(int a, int b) { return a + b; }
+            int main(void) { int x = -1; while (x) x = !x; return add(x, 2); }";
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        semantics::label_loops(&mut program).unwrap();
+        assert_eq!(
+            crate::gen_tacky(&program).to_string(),
+            "add(a, b):
+    tmp.0 = a + b
+    return tmp.0
+main():
+    tmp.0 = -1
+    x = tmp.0
+  main.while.0.continue:
+    jump_if_zero x, main.while.0.break
+    tmp.1 = !x
+    x = tmp.1
+    jump main.while.0.continue
+  main.while.0.break:
+    tmp.2 = add(x, 2)
+    return tmp.2
+"
+        );
+    }
+}
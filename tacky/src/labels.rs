@@ -0,0 +1,32 @@
+//! Naming of the jump targets introduced while lowering control flow.
+
+/// Makes the labels for a single function.
+///
+/// Labels look like `main.while_break.3`: the function they belong to, the
+/// construct they implement, and a counter. Since the counter restarts for every
+/// function, editing one function doesn't rename the labels in any other, which
+/// keeps diffs of the generated assembly small. The function name keeps labels
+/// unique across the whole program, as C identifiers never contain a `.`.
+///
+/// Loops and switches are labeled the same way by `semantics::label_loops` instead,
+/// and their jump targets add `.start`, `.continue` or `.break` to the label.
+pub(crate) struct LabelGenerator {
+    function_name: String,
+    counter: usize,
+}
+
+impl LabelGenerator {
+    pub(crate) fn new(function_name: &str) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            counter: 0,
+        }
+    }
+
+    /// Makes a new label for `construct`, e.g. `"if_else"`
+    pub(crate) fn make_label(&mut self, construct: &str) -> String {
+        let label = format!("{}.{construct}.{}", self.function_name, self.counter);
+        self.counter += 1;
+        label
+    }
+}
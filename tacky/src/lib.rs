@@ -0,0 +1,833 @@
+//! Lowering of the AST into TACKY, following chapter 2 onwards of the book.
+
+use parser::ast::{self, BlockItem, Declaration, Expression, ForInit, Program, Statement};
+
+mod fold;
+pub mod ir;
+mod labels;
+
+pub use fold::fold_constants;
+
+use ir::{BinaryOp, Function, Instruction, TackyProgram, UnaryOp, Val};
+use labels::LabelGenerator;
+
+/// Lowers `program` into TACKY.
+///
+/// Functions that are only declared produce no TACKY. Every value is currently
+/// treated as an `int`, so casts lower to their operand.
+///
+/// # Panics
+///
+/// Rejecting invalid programs is left to semantic analysis, so this panics on an
+/// assignment to something other than a variable. It also panics if loops and
+/// switches were not labeled by `semantics::label_loops`, which rejects `break` and
+/// `continue` outside of a loop, and `case` and `default` outside of a switch.
+pub fn gen_tacky(program: &Program) -> TackyProgram {
+    let functions = program
+        .declarations
+        .iter()
+        .filter_map(|declaration| match declaration {
+            Declaration::Function(function) => Generator::new(&function.name).function(function),
+            Declaration::Variable(_) => None,
+        })
+        .collect();
+    TackyProgram { functions }
+}
+
+/// Lowers a single function
+struct Generator {
+    /// Number of temporaries made so far
+    temporaries: usize,
+    labels: LabelGenerator,
+    instructions: Vec<Instruction>,
+}
+
+impl Generator {
+    fn new(function_name: &str) -> Self {
+        Self {
+            temporaries: 0,
+            labels: LabelGenerator::new(function_name),
+            instructions: Vec::new(),
+        }
+    }
+
+    fn function(mut self, function: &ast::FunctionDefinition) -> Option<Function> {
+        let body = function.body.as_ref()?;
+        self.statement(body);
+
+        let mut body = self.instructions;
+        // Falling off the end of a function returns 0. This is only required for
+        // `main`, and is harmless elsewhere since the return value is unspecified.
+        if !matches!(body.last(), Some(Instruction::Return(_))) {
+            body.push(Instruction::Return(Val::Constant(0)));
+        }
+
+        Some(Function {
+            name: function.name.clone(),
+            params: function
+                .params
+                .iter()
+                .map(|(_, name)| {
+                    // Unwrap is safe, as the parser requires names in definitions
+                    name.clone().unwrap()
+                })
+                .collect(),
+            body,
+        })
+    }
+
+    fn make_temporary(&mut self) -> Val {
+        let name = format!("tmp.{}", self.temporaries);
+        self.temporaries += 1;
+        Val::Var(name)
+    }
+
+    fn make_label(&mut self, construct: &str) -> String {
+        self.labels.make_label(construct)
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    fn block_item(&mut self, item: &BlockItem) {
+        match item {
+            BlockItem::Statement(statement) => self.statement(statement),
+            BlockItem::Declaration(Declaration::Variable(declaration)) => {
+                self.variable_declaration(declaration)
+            }
+            // Local function declarations have no code
+            BlockItem::Declaration(Declaration::Function(_)) => {}
+        }
+    }
+
+    fn variable_declaration(&mut self, declaration: &ast::VariableDeclaration) {
+        if let Some(init) = &declaration.init {
+            let src = self.expression(init);
+            self.emit(Instruction::Copy {
+                src,
+                dst: Val::Var(declaration.name.clone()),
+            });
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Return(exp) => {
+                let val = self.expression(exp);
+                self.emit(Instruction::Return(val));
+            }
+            Statement::Expression(exp) => {
+                self.expression(exp);
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let end_label = self.make_label("if_end");
+                let condition = self.expression(condition);
+                match otherwise {
+                    Some(otherwise) => {
+                        let else_label = self.make_label("if_else");
+                        self.emit(Instruction::JumpIfZero {
+                            condition,
+                            target: else_label.clone(),
+                        });
+                        self.statement(then);
+                        self.emit(Instruction::Jump(end_label.clone()));
+                        self.emit(Instruction::Label(else_label));
+                        self.statement(otherwise);
+                    }
+                    None => {
+                        self.emit(Instruction::JumpIfZero {
+                            condition,
+                            target: end_label.clone(),
+                        });
+                        self.statement(then);
+                    }
+                }
+                self.emit(Instruction::Label(end_label));
+            }
+            Statement::Compound(items) => {
+                for item in items {
+                    self.block_item(item);
+                }
+            }
+            Statement::Break { label } => {
+                self.emit(Instruction::Jump(break_label(label)));
+            }
+            Statement::Continue { label } => {
+                self.emit(Instruction::Jump(continue_label(label)));
+            }
+            Statement::While {
+                condition,
+                body,
+                label,
+            } => {
+                let continue_label = continue_label(label);
+                let break_label = break_label(label);
+                self.emit(Instruction::Label(continue_label.clone()));
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfZero {
+                    condition,
+                    target: break_label.clone(),
+                });
+                self.statement(body);
+                self.emit(Instruction::Jump(continue_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            Statement::DoWhile {
+                body,
+                condition,
+                label,
+            } => {
+                let start_label = start_label(label);
+                self.emit(Instruction::Label(start_label.clone()));
+                self.statement(body);
+                self.emit(Instruction::Label(continue_label(label)));
+                let condition = self.expression(condition);
+                self.emit(Instruction::JumpIfNotZero {
+                    condition,
+                    target: start_label,
+                });
+                self.emit(Instruction::Label(break_label(label)));
+            }
+            Statement::For {
+                init,
+                condition,
+                post,
+                body,
+                label,
+            } => {
+                let start_label = start_label(label);
+                let break_label = break_label(label);
+                match init {
+                    ForInit::Declaration(declaration) => self.variable_declaration(declaration),
+                    ForInit::Expression(exp) => {
+                        if let Some(exp) = exp {
+                            self.expression(exp);
+                        }
+                    }
+                }
+                self.emit(Instruction::Label(start_label.clone()));
+                // A missing condition is always true
+                if let Some(condition) = condition {
+                    let condition = self.expression(condition);
+                    self.emit(Instruction::JumpIfZero {
+                        condition,
+                        target: break_label.clone(),
+                    });
+                }
+                self.statement(body);
+                self.emit(Instruction::Label(continue_label(label)));
+                if let Some(post) = post {
+                    self.expression(post);
+                }
+                self.emit(Instruction::Jump(start_label));
+                self.emit(Instruction::Label(break_label));
+            }
+            Statement::Switch {
+                control,
+                body,
+                label,
+                cases,
+            } => {
+                let break_label = break_label(label);
+                let control = self.expression(control);
+                // Compare against each case in turn, and if none match, jump to the
+                // default, or out of the switch if it has none
+                let mut otherwise = break_label.clone();
+                for (value, case_label) in cases {
+                    let Some(value) = value else {
+                        otherwise = case_label.clone();
+                        continue;
+                    };
+                    let matches = self.make_temporary();
+                    self.emit(Instruction::Binary {
+                        op: BinaryOp::Equal,
+                        src1: control.clone(),
+                        src2: Val::Constant(*value),
+                        dst: matches.clone(),
+                    });
+                    self.emit(Instruction::JumpIfNotZero {
+                        condition: matches,
+                        target: case_label.clone(),
+                    });
+                }
+                self.emit(Instruction::Jump(otherwise));
+                self.statement(body);
+                self.emit(Instruction::Label(break_label));
+            }
+            Statement::Case { stmt, label, .. } | Statement::Default { stmt, label } => {
+                self.emit(Instruction::Label(
+                    label.clone().expect("switches are labeled"),
+                ));
+                self.statement(stmt);
+            }
+            Statement::Goto(target) => self.emit(Instruction::Jump(target.clone())),
+            Statement::Labeled { name, stmt } => {
+                self.emit(Instruction::Label(name.clone()));
+                self.statement(stmt);
+            }
+            Statement::Null => {}
+        }
+    }
+
+    /// Emits the instructions evaluating `exp`, and returns where its value ends up.
+    fn expression(&mut self, exp: &Expression) -> Val {
+        match exp {
+            Expression::Constant(value) => Val::Constant(*value),
+            Expression::Var(name) => Val::Var(name.clone()),
+            Expression::Unary { op, expr } => {
+                let src = self.expression(expr);
+                let dst = self.make_temporary();
+                self.emit(Instruction::Unary {
+                    op: unary_op(*op),
+                    src,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Expression::Binary {
+                op: op @ (ast::BinaryOp::And | ast::BinaryOp::Or),
+                left,
+                right,
+            } => self.short_circuit(*op == ast::BinaryOp::And, left, right),
+            Expression::Binary {
+                op: ast::BinaryOp::Comma,
+                left,
+                right,
+            } => {
+                // The left operand is only evaluated for its side effects
+                self.expression(left);
+                self.expression(right)
+            }
+            Expression::Binary { op, left, right } => {
+                let src1 = self.expression(left);
+                let src2 = self.expression(right);
+                let dst = self.make_temporary();
+                self.emit(Instruction::Binary {
+                    op: binary_op(*op),
+                    src1,
+                    src2,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Expression::Assignment { target, value } => {
+                let Expression::Var(name) = target.as_ref() else {
+                    panic!("assignment to something other than a variable");
+                };
+                let src = self.expression(value);
+                let dst = Val::Var(name.clone());
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Expression::Conditional {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let else_label = self.make_label("cond_else");
+                let end_label = self.make_label("cond_end");
+                let dst = self.make_temporary();
+                let condition = self.expression(cond);
+                self.emit(Instruction::JumpIfZero {
+                    condition,
+                    target: else_label.clone(),
+                });
+                let src = self.expression(then);
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                self.emit(Instruction::Jump(end_label.clone()));
+                self.emit(Instruction::Label(else_label));
+                let src = self.expression(otherwise);
+                self.emit(Instruction::Copy {
+                    src,
+                    dst: dst.clone(),
+                });
+                self.emit(Instruction::Label(end_label));
+                dst
+            }
+            Expression::Cast { expr, .. } => self.expression(expr),
+            Expression::FunctionCall { name, args } => {
+                let args = args.iter().map(|arg| self.expression(arg)).collect();
+                let dst = self.make_temporary();
+                self.emit(Instruction::FunCall {
+                    name: name.clone(),
+                    args,
+                    dst: dst.clone(),
+                });
+                dst
+            }
+            Expression::Expect { expr, expected } => {
+                let value = self.expression(expr);
+                // The hint is only used for its side effects, as no pass lays out
+                // branches by likelihood yet
+                self.expression(expected);
+                value
+            }
+        }
+    }
+
+    /// Lowers `left && right` (if `is_and`) or `left || right` into jumps, so that
+    /// `right` is only evaluated when it decides the result.
+    fn short_circuit(&mut self, is_and: bool, left: &Expression, right: &Expression) -> Val {
+        let (description, short_circuit_value) = if is_and {
+            ("and_false", 0)
+        } else {
+            ("or_true", 1)
+        };
+        let short_circuit_label = self.make_label(description);
+        let end_label = self.make_label(if is_and { "and_end" } else { "or_end" });
+        let dst = self.make_temporary();
+
+        for operand in [left, right] {
+            let condition = self.expression(operand);
+            let target = short_circuit_label.clone();
+            self.emit(if is_and {
+                Instruction::JumpIfZero { condition, target }
+            } else {
+                Instruction::JumpIfNotZero { condition, target }
+            });
+        }
+        self.emit(Instruction::Copy {
+            src: Val::Constant(1 - short_circuit_value),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Jump(end_label.clone()));
+        self.emit(Instruction::Label(short_circuit_label));
+        self.emit(Instruction::Copy {
+            src: Val::Constant(short_circuit_value),
+            dst: dst.clone(),
+        });
+        self.emit(Instruction::Label(end_label));
+        dst
+    }
+}
+
+fn unary_op(op: ast::UnaryOp) -> UnaryOp {
+    match op {
+        ast::UnaryOp::Negate => UnaryOp::Negate,
+        ast::UnaryOp::Complement => UnaryOp::Complement,
+        ast::UnaryOp::Not => UnaryOp::Not,
+    }
+}
+
+/// Converts a non-short-circuiting binary operator.
+fn binary_op(op: ast::BinaryOp) -> BinaryOp {
+    match op {
+        ast::BinaryOp::Add => BinaryOp::Add,
+        ast::BinaryOp::Subtract => BinaryOp::Subtract,
+        ast::BinaryOp::Multiply => BinaryOp::Multiply,
+        ast::BinaryOp::Divide => BinaryOp::Divide,
+        ast::BinaryOp::Remainder => BinaryOp::Remainder,
+        ast::BinaryOp::BitwiseAnd => BinaryOp::BitwiseAnd,
+        ast::BinaryOp::BitwiseOr => BinaryOp::BitwiseOr,
+        ast::BinaryOp::BitwiseXor => BinaryOp::BitwiseXor,
+        ast::BinaryOp::LeftShift => BinaryOp::LeftShift,
+        ast::BinaryOp::RightShift => BinaryOp::RightShift,
+        ast::BinaryOp::Equal => BinaryOp::Equal,
+        ast::BinaryOp::NotEqual => BinaryOp::NotEqual,
+        ast::BinaryOp::LessThan => BinaryOp::LessThan,
+        ast::BinaryOp::LessOrEqual => BinaryOp::LessOrEqual,
+        ast::BinaryOp::GreaterThan => BinaryOp::GreaterThan,
+        ast::BinaryOp::GreaterOrEqual => BinaryOp::GreaterOrEqual,
+        ast::BinaryOp::And | ast::BinaryOp::Or => unreachable!("`{op:?}` short-circuits"),
+        ast::BinaryOp::Comma => unreachable!("the comma operator is lowered separately"),
+    }
+}
+
+/// Returns the label of a loop or switch, which is set by loop labeling.
+fn loop_label(label: &Option<String>) -> &str {
+    label.as_deref().expect("loops are labeled")
+}
+
+/// Label at the top of a loop whose condition is checked at the bottom (or not at all)
+fn start_label(label: &Option<String>) -> String {
+    format!("{}.start", loop_label(label))
+}
+
+/// Label that `continue` jumps to, which precedes the condition and post-expression
+fn continue_label(label: &Option<String>) -> String {
+    format!("{}.continue", loop_label(label))
+}
+
+/// Label that `break` jumps to, just past the loop or switch
+fn break_label(label: &Option<String>) -> String {
+    format!("{}.break", loop_label(label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tacky_str(input: &str) -> TackyProgram {
+        let mut program = parser::parse(lexer::tokenize_str(input).unwrap()).unwrap();
+        semantics::label_loops(&mut program).unwrap();
+        semantics::resolve_labels(&mut program).unwrap();
+        gen_tacky(&program)
+    }
+
+    /// Lowers `body` as the body of `main`, and returns its instructions
+    fn body_tacky_str(body: &str) -> Vec<Instruction> {
+        let mut program = tacky_str(&format!("int main(void) {{ {body} }}"));
+        assert_eq!(program.functions.len(), 1);
+        program.functions.remove(0).body
+    }
+
+    fn var(name: &str) -> Val {
+        Val::Var(name.to_string())
+    }
+
+    fn label(name: &str) -> Instruction {
+        Instruction::Label(name.to_string())
+    }
+
+    #[test]
+    fn test_gen_tacky_unary() {
+        assert_eq!(
+            tacky_str("int main(void) { return -(-5); }"),
+            TackyProgram {
+                functions: vec![Function {
+                    name: "main".to_string(),
+                    params: vec![],
+                    body: vec![
+                        Instruction::Unary {
+                            op: UnaryOp::Negate,
+                            src: Val::Constant(5),
+                            dst: var("tmp.0"),
+                        },
+                        Instruction::Unary {
+                            op: UnaryOp::Negate,
+                            src: var("tmp.0"),
+                            dst: var("tmp.1"),
+                        },
+                        Instruction::Return(var("tmp.1")),
+                    ],
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_binary() {
+        assert_eq!(
+            body_tacky_str("int x = 1 + 2 * 3; x = x - 1;"),
+            vec![
+                Instruction::Binary {
+                    op: BinaryOp::Multiply,
+                    src1: Val::Constant(2),
+                    src2: Val::Constant(3),
+                    dst: var("tmp.0"),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src1: Val::Constant(1),
+                    src2: var("tmp.0"),
+                    dst: var("tmp.1"),
+                },
+                Instruction::Copy {
+                    src: var("tmp.1"),
+                    dst: var("x"),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Subtract,
+                    src1: var("x"),
+                    src2: Val::Constant(1),
+                    dst: var("tmp.2"),
+                },
+                Instruction::Copy {
+                    src: var("tmp.2"),
+                    dst: var("x"),
+                },
+                // Falling off the end of main returns 0
+                Instruction::Return(Val::Constant(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_short_circuit() {
+        assert_eq!(
+            body_tacky_str("return a && b;"),
+            vec![
+                Instruction::JumpIfZero {
+                    condition: var("a"),
+                    target: "main.and_false.0".to_string(),
+                },
+                Instruction::JumpIfZero {
+                    condition: var("b"),
+                    target: "main.and_false.0".to_string(),
+                },
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: var("tmp.0"),
+                },
+                Instruction::Jump("main.and_end.1".to_string()),
+                label("main.and_false.0"),
+                Instruction::Copy {
+                    src: Val::Constant(0),
+                    dst: var("tmp.0"),
+                },
+                label("main.and_end.1"),
+                Instruction::Return(var("tmp.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_logical_result_in_arithmetic() {
+        let body = body_tacky_str("return (1 && 1) + (0 || 1);");
+        // Both operators produce a temporary holding 0 or 1, which the addition reads
+        assert_eq!(
+            body[body.len() - 2..],
+            [
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src1: var("tmp.0"),
+                    src2: var("tmp.1"),
+                    dst: var("tmp.2"),
+                },
+                Instruction::Return(var("tmp.2")),
+            ]
+        );
+        assert_eq!(
+            body.iter()
+                .filter(|instruction| matches!(
+                    instruction,
+                    Instruction::Copy { dst, .. } if *dst == var("tmp.1")
+                ))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_comma() {
+        assert_eq!(
+            body_tacky_str("int x = 0; return (x = 1, x + 1);"),
+            vec![
+                Instruction::Copy {
+                    src: Val::Constant(0),
+                    dst: var("x"),
+                },
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: var("x"),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Add,
+                    src1: var("x"),
+                    src2: Val::Constant(1),
+                    dst: var("tmp.0"),
+                },
+                Instruction::Return(var("tmp.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_switch() {
+        assert_eq!(
+            body_tacky_str("switch (x) { case 1: return 1; default: break; case 2: x = 2; }"),
+            vec![
+                Instruction::Binary {
+                    op: BinaryOp::Equal,
+                    src1: var("x"),
+                    src2: Val::Constant(1),
+                    dst: var("tmp.0"),
+                },
+                Instruction::JumpIfNotZero {
+                    condition: var("tmp.0"),
+                    target: "main.switch.0.case.0".to_string(),
+                },
+                Instruction::Binary {
+                    op: BinaryOp::Equal,
+                    src1: var("x"),
+                    src2: Val::Constant(2),
+                    dst: var("tmp.1"),
+                },
+                Instruction::JumpIfNotZero {
+                    condition: var("tmp.1"),
+                    target: "main.switch.0.case.2".to_string(),
+                },
+                Instruction::Jump("main.switch.0.default".to_string()),
+                label("main.switch.0.case.0"),
+                Instruction::Return(Val::Constant(1)),
+                label("main.switch.0.default"),
+                Instruction::Jump("main.switch.0.break".to_string()),
+                label("main.switch.0.case.2"),
+                Instruction::Copy {
+                    src: Val::Constant(2),
+                    dst: var("x"),
+                },
+                label("main.switch.0.break"),
+                Instruction::Return(Val::Constant(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_goto() {
+        assert_eq!(
+            body_tacky_str("goto end; x = 1; end: return x;"),
+            vec![
+                Instruction::Jump("main.label.end".to_string()),
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: var("x"),
+                },
+                label("main.label.end"),
+                Instruction::Return(var("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_loops() {
+        assert_eq!(
+            body_tacky_str("while (x) { if (y) break; else continue; }"),
+            vec![
+                label("main.while.0.continue"),
+                Instruction::JumpIfZero {
+                    condition: var("x"),
+                    target: "main.while.0.break".to_string(),
+                },
+                Instruction::JumpIfZero {
+                    condition: var("y"),
+                    target: "main.if_else.1".to_string(),
+                },
+                Instruction::Jump("main.while.0.break".to_string()),
+                Instruction::Jump("main.if_end.0".to_string()),
+                label("main.if_else.1"),
+                Instruction::Jump("main.while.0.continue".to_string()),
+                label("main.if_end.0"),
+                Instruction::Jump("main.while.0.continue".to_string()),
+                label("main.while.0.break"),
+                Instruction::Return(Val::Constant(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_for_short_circuit_condition() {
+        // The `&&` is lowered with its own labels, entirely between the loop's start and
+        // the jump out of the loop
+        assert_eq!(
+            body_tacky_str("for (; a && b;) ;"),
+            vec![
+                label("main.for.0.start"),
+                Instruction::JumpIfZero {
+                    condition: var("a"),
+                    target: "main.and_false.0".to_string(),
+                },
+                Instruction::JumpIfZero {
+                    condition: var("b"),
+                    target: "main.and_false.0".to_string(),
+                },
+                Instruction::Copy {
+                    src: Val::Constant(1),
+                    dst: var("tmp.0"),
+                },
+                Instruction::Jump("main.and_end.1".to_string()),
+                label("main.and_false.0"),
+                Instruction::Copy {
+                    src: Val::Constant(0),
+                    dst: var("tmp.0"),
+                },
+                label("main.and_end.1"),
+                Instruction::JumpIfZero {
+                    condition: var("tmp.0"),
+                    target: "main.for.0.break".to_string(),
+                },
+                label("main.for.0.continue"),
+                Instruction::Jump("main.for.0.start".to_string()),
+                label("main.for.0.break"),
+                Instruction::Return(Val::Constant(0)),
+            ]
+        );
+
+        // Every label is defined once, even with nested loops and conditionals
+        let body =
+            body_tacky_str("for (; a ? b || c : d;) for (; a && b;) { if (c ? 1 : 0) break; }");
+        let labels = body
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Label(label) => Some(label),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let unique = labels.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(labels.len(), unique.len(), "{labels:?}");
+    }
+
+    #[test]
+    fn test_gen_tacky_functions() {
+        let program = tacky_str(
+            "int add(int a, int b);
+            int add(int a, int b) { return a + b; }
+            int main(void) { return add(1, 2); }",
+        );
+        assert_eq!(
+            program
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f.params.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("add", vec!["a".to_string(), "b".to_string()]),
+                ("main", vec![])
+            ]
+        );
+        assert_eq!(
+            program.functions[1].body,
+            vec![
+                Instruction::FunCall {
+                    name: "add".to_string(),
+                    args: vec![Val::Constant(1), Val::Constant(2)],
+                    dst: var("tmp.0"),
+                },
+                Instruction::Return(var("tmp.0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_gen_tacky_labels() {
+        let source = "int f(void) { for (;;) break; return 0; }
+            int main(void) { do ; while (1 ? f() : 0); return 0; }";
+        let program = tacky_str(source);
+        assert_eq!(program, tacky_str(source));
+
+        let labels = program
+            .functions
+            .iter()
+            .flat_map(|f| &f.body)
+            .filter_map(|instruction| match instruction {
+                Instruction::Label(label) => Some(label.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Each function counts from zero
+        assert_eq!(
+            labels,
+            vec![
+                "f.for.0.start",
+                "f.for.0.continue",
+                "f.for.0.break",
+                "main.do.0.start",
+                "main.do.0.continue",
+                "main.cond_else.0",
+                "main.cond_end.1",
+                "main.do.0.break",
+            ]
+        );
+    }
+}